@@ -0,0 +1,82 @@
+//! Measures how the cost of the probe grows with the number of endpoints.
+//!
+//! Every iteration starts discovering endpoints from a lookup returning `n` endpoints,
+//! and drains the changes until the probe has caught up:
+//! * `fresh`: a single probe reports `n` inserts;
+//! * `rolling`: a first probe reports `n` inserts, then a second probe diffs the `n`
+//!   known endpoints against a resolution where 1% of them have been replaced.
+//!
+//! The gap between the two cases is the cost of diffing a large, mostly stable, set.
+//! The changes are drained through [`LoadBalancedChannelBuilder::discover`] because a
+//! balanced channel only consumes them once it is called.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use futures::StreamExt;
+use ginepro::{LoadBalancedChannel, LookupService, ServiceDefinition};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Returns `n` endpoints, replacing the first `rolled` of them after the first resolution.
+#[derive(Clone)]
+struct RollingLookup {
+    n: u16,
+    rolled: u16,
+    resolutions: Arc<AtomicU8>,
+}
+
+#[async_trait::async_trait]
+impl LookupService for RollingLookup {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let generation = self.resolutions.fetch_add(1, Ordering::Relaxed).min(1);
+
+        Ok((1..=self.n)
+            .map(|i| {
+                let [a, b] = i.to_be_bytes();
+                let generation = if i <= self.rolled { generation } else { 0 };
+                SocketAddr::from(([10, generation, a, b], 5000))
+            })
+            .collect())
+    }
+}
+
+fn create_changeset(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to build runtime");
+    let mut group = c.benchmark_group("create_changeset");
+    group.sample_size(10);
+
+    for n in [1_000, 10_000] {
+        for (case, rolled, changes) in [("fresh", 0, n), ("rolling", n / 100, n + n / 50)] {
+            group.bench_with_input(BenchmarkId::new(case, n), &n, |b, &n| {
+                b.to_async(&runtime).iter(|| async move {
+                    let lookup = RollingLookup {
+                        n,
+                        rolled,
+                        resolutions: Arc::new(AtomicU8::new(0)),
+                    };
+                    let discover = LoadBalancedChannel::builder(("test", 5000))
+                        .lookup_service(lookup)
+                        .dns_probe_interval(Duration::from_millis(1))
+                        .discover()
+                        .await
+                        .expect("failed to init");
+
+                    discover
+                        .take(usize::from(changes))
+                        .for_each(|_| async {})
+                        .await
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, create_changeset);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+//! Measures how long it takes to report a full replacement of 1000 endpoints to tonic.
+//!
+//! Every iteration builds an eagerly resolved channel whose lookup returns 1000 endpoints
+//! that have not been seen before, so the single probe reports 1000 inserts.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use ginepro::{LoadBalancedChannel, LookupService, ResolutionStrategy, ServiceDefinition};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const NUM_ENDPOINTS: u16 = 1000;
+
+/// Returns a fresh set of `NUM_ENDPOINTS` endpoints on every resolution.
+#[derive(Clone, Default)]
+struct ChurningLookup {
+    generation: Arc<AtomicU16>,
+}
+
+#[async_trait::async_trait]
+impl LookupService for ChurningLookup {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed);
+        let [a, b] = generation.to_be_bytes();
+
+        Ok((1..=NUM_ENDPOINTS)
+            .map(|port| SocketAddr::from(([10, a, b, 1], port)))
+            .collect())
+    }
+}
+
+fn report_changeset(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("failed to build runtime");
+    let lookup = ChurningLookup::default();
+
+    c.bench_function("report 1000 endpoint changeset", |b| {
+        b.to_async(&runtime).iter(|| async {
+            LoadBalancedChannel::builder(("test", 5000))
+                .lookup_service(lookup.clone())
+                .dns_probe_interval(Duration::from_secs(3600))
+                .resolution_strategy(ResolutionStrategy::Eager {
+                    timeout: Duration::from_secs(10),
+                })
+                .channel()
+                .await
+                .expect("failed to init")
+        })
+    });
+}
+
+criterion_group!(benches, report_changeset);
+criterion_main!(benches);
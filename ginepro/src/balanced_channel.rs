@@ -2,20 +2,36 @@
 //! periodic service discovery.
 
 use crate::{
-    service_probe::{GrpcServiceProbe, GrpcServiceProbeConfig},
-    DnsResolver, LookupService, ServiceDefinition,
+    runtime::{DefaultRuntime, Runtime},
+    service_probe::{
+        tls_mismatch_hint, AddressRewriteFn, EndpointLayerFn, EndpointUriFn, EndpointsCallback,
+        FailuresCallback, GrpcServiceProbe, GrpcServiceProbeConfig, ProbeState,
+    },
+    DiscoverySnapshot, DnsRecordKind, DnsResolver, EndpointDiscover, LookupService, ProbeObserver,
+    ServiceDefinition, StaticLookupService,
 };
 use anyhow::Context as _;
+use futures::StreamExt;
 use http::Request;
 use std::{
+    collections::HashSet,
     convert::TryInto,
-    task::{Context, Poll},
+    future::Future,
+    net::SocketAddr,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    task::{ready, Context, Poll},
 };
-use tokio::time::Duration;
+use tokio::{sync::mpsc::Sender, time::Duration};
 use tonic::client::GrpcService;
-use tonic::transport::channel::Channel;
-use tonic::{body::BoxBody, transport::ClientTlsConfig};
-use tower::Service;
+use tonic::transport::channel::{Channel, Endpoint};
+use tonic::{body::BoxBody, transport::ClientTlsConfig, Status};
+use tower::{
+    buffer::Buffer, discover::Change, load_shed::error::Overloaded, timeout::error::Elapsed,
+    util::BoxService, BoxError, Layer, Service, ServiceBuilder, ServiceExt,
+};
+use tracing::Instrument;
 
 // Determines the channel size of the channel we use
 // to report endpoint changes to tonic.
@@ -23,6 +39,27 @@ use tower::Service;
 // We set the number high to avoid any blocking on our side.
 static GRPC_REPORT_ENDPOINTS_CHANNEL_SIZE: usize = 1024;
 
+// The hostname of the placeholder service definition of the channels
+// built over a fixed list of endpoints.
+static STATIC_ENDPOINTS_HOSTNAME: &str = "static.endpoints";
+
+// How often the service definitions are resolved, unless configured otherwise.
+static DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Determines how many requests can be queued in front of
+// the channel-level layers, matching tonic's own channel buffer.
+static LAYERED_CHANNEL_BUFFER_SIZE: usize = 1024;
+
+/// A [`Channel`] wrapped by the channel-level layers.
+type LayeredChannel = Buffer<BoxedChannelService, Request<BoxBody>>;
+
+/// A type-erased service wrapping a [`Channel`].
+type BoxedChannelService = BoxService<Request<BoxBody>, http::Response<BoxBody>, BoxError>;
+
+/// Applies the layer set with [`LoadBalancedChannelBuilder::with_channel_layer`],
+/// to the balanced channel and to the channel of a pinned endpoint.
+type ChannelLayerFn = Arc<dyn Fn(Channel) -> BoxedChannelService + Send + Sync>;
+
 /// Implements tonic [`GrpcService`] for a client-side load balanced [`Channel`] (using `The Power of
 /// Two Choices`).
 ///
@@ -44,12 +81,87 @@ static GRPC_REPORT_ENDPOINTS_CHANNEL_SIZE: usize = 1024;
 /// }
 /// ```
 ///
-#[derive(Debug, Clone)]
-pub struct LoadBalancedChannel(Channel);
+#[derive(Clone)]
+pub struct LoadBalancedChannel {
+    balanced: Route,
+    layers: ChannelLayers,
+    /// The endpoint the channel and all its clones are pinned to, if any.
+    pinned: Arc<RwLock<Option<(SocketAddr, Route)>>>,
+    /// Where the next request goes, decided when the channel is polled for readiness.
+    ready: ReadyRoute,
+    settings: ChannelSettings,
+    state: Arc<ProbeState>,
+}
+
+impl std::fmt::Debug for LoadBalancedChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pinned = self
+            .pinned
+            .read()
+            .expect("pinned endpoint lock poisoned")
+            .as_ref()
+            .map(|(address, _)| *address);
+
+        f.debug_struct("LoadBalancedChannel")
+            .field("channel", &self.balanced.channel)
+            .field("layered", &self.balanced.layered.is_some())
+            .field("pinned", &pinned)
+            .field("settings", &self.settings)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+/// The settings a channel has been constructed with, once the defaults have been applied.
+#[derive(Debug, Clone, Copy)]
+struct ChannelSettings {
+    probe_interval: Duration,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    reprobe_on_unavailable: bool,
+}
 
+/// A [`Channel`] and the channel-level layers wrapping it, if any.
+#[derive(Clone)]
+struct Route {
+    channel: Channel,
+    /// Set when channel-level layers have been configured, in which case
+    /// every request goes through them instead of directly to `channel`.
+    layered: Option<LayeredChannel>,
+}
+
+impl Route {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        match self.layered {
+            Some(ref mut layered) => Service::poll_ready(layered, cx),
+            None => GrpcService::poll_ready(&mut self.channel, cx).map_err(Into::into),
+        }
+    }
+
+    fn call(&mut self, request: Request<BoxBody>) -> ResponseFutureInner {
+        match self.layered {
+            Some(ref mut layered) => ResponseFutureInner::Layered(Service::call(layered, request)),
+            None => ResponseFutureInner::Channel(GrpcService::call(&mut self.channel, request)),
+        }
+    }
+}
+
+/// The route a [`LoadBalancedChannel`] has been made ready for.
+#[derive(Clone)]
+enum ReadyRoute {
+    Balanced,
+    Pinned(SocketAddr, Route),
+    /// The channel is pinned to an endpoint that is no longer discovered.
+    Absent(SocketAddr),
+}
+
+/// Get the balanced [`Channel`].
+///
+/// The returned [`Channel`] bypasses the channel-level layers, as
+/// [`LoadBalancedChannel::channel`] does.
 impl From<LoadBalancedChannel> for Channel {
     fn from(channel: LoadBalancedChannel) -> Self {
-        channel.0
+        channel.channel()
     }
 }
 
@@ -67,20 +179,520 @@ impl LoadBalancedChannel {
     {
         LoadBalancedChannelBuilder::new_with_service(service_definition)
     }
+
+    /// Start configuring a `LoadBalancedChannel` over a fixed list of addresses,
+    /// without any DNS resolution or hostname validation.
+    ///
+    /// The endpoints are resolved by a [`StaticLookupService`], under a placeholder
+    /// [`ServiceDefinition`] for the hostname `static.endpoints`.
+    /// Since the tls certificates of the endpoints are verified against that hostname,
+    /// a tls channel has to disable [`LoadBalancedChannelBuilder::override_tls_domain_name`]
+    /// and set the domain name of its configuration.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::from_endpoints([
+    ///         "10.0.0.1:5000".parse().unwrap(),
+    ///         "10.0.0.2:5000".parse().unwrap(),
+    ///     ])
+    ///     .channel()
+    ///     .await
+    ///     .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn from_endpoints(
+        endpoints: impl IntoIterator<Item = SocketAddr>,
+    ) -> LoadBalancedChannelBuilder<StaticLookupService, ServiceDefinition> {
+        let service_definition = ServiceDefinition::from_parts(STATIC_ENDPOINTS_HOSTNAME, 1)
+            .expect("the placeholder service definition is valid");
+
+        LoadBalancedChannelBuilder::new_with_service(service_definition)
+            .lookup_service(StaticLookupService::new(endpoints))
+    }
+
+    /// Register an additional [`ServiceDefinition`] on a live channel.
+    ///
+    /// The endpoints of every registered [`ServiceDefinition`] are merged into the single
+    /// set of endpoints this channel balances over. The new definition is resolved from the
+    /// next probe onwards. Registering a definition that is already present is a no-op.
+    ///
+    /// When tls is enabled the domain name of the original [`ServiceDefinition`] is used
+    /// to verify every endpoint.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("blue.hostname", 5000))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    ///
+    ///     load_balanced_channel
+    ///         .add_service_definition(("green.hostname", 5000))
+    ///         .expect("invalid service definition");
+    /// }
+    /// ```
+    pub fn add_service_definition<S>(&self, service_definition: S) -> Result<(), anyhow::Error>
+    where
+        S: TryInto<ServiceDefinition>,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send + Sync,
+    {
+        let service_definition = service_definition
+            .try_into()
+            .map_err(Into::into)
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        self.state.add_service_definition(service_definition);
+
+        Ok(())
+    }
+
+    /// Unregister a [`ServiceDefinition`] from a live channel.
+    ///
+    /// Its endpoints are removed from the channel on the next probe, unless they are
+    /// also resolved by another registered definition.
+    /// Returns `false` if the definition was not registered.
+    pub fn remove_service_definition(&self, service_definition: &ServiceDefinition) -> bool {
+        self.state.remove_service_definition(service_definition)
+    }
+
+    /// Get the [`ServiceDefinition`] this channel has been constructed with, e.g. to key
+    /// a pool of channels by it.
+    ///
+    /// Its hostname is the domain name the endpoints are verified against when tls is enabled,
+    /// and it is still returned if it has been unregistered with
+    /// [`Self::remove_service_definition`]. See [`Self::service_definitions`] for the ones
+    /// currently resolved.
+    pub fn service_definition(&self) -> &ServiceDefinition {
+        self.state.service_definition()
+    }
+
+    /// Get the [`ServiceDefinition`]s currently resolved by this channel.
+    pub fn service_definitions(&self) -> Vec<ServiceDefinition> {
+        self.state.service_definitions()
+    }
+
+    /// Get a clone of the balanced [`Channel`], e.g. to hand it to a generated client
+    /// while keeping the [`LoadBalancedChannel`] around for introspection.
+    ///
+    /// Like the [`Channel`] obtained through `From`, the returned channel bypasses
+    /// the channel-level settings, and a warning is logged if any is configured:
+    /// [`LoadBalancedChannelBuilder::with_channel_layer`],
+    /// [`LoadBalancedChannelBuilder::overall_timeout`],
+    /// [`LoadBalancedChannelBuilder::global_concurrency_limit`] and
+    /// [`LoadBalancedChannelBuilder::default_headers`]. In particular, when
+    /// [`LoadBalancedChannelBuilder::timeout_per_try`] is set, the request timeout is only
+    /// enforced at the channel level: the returned channel only bounds every try.
+    pub fn channel(&self) -> Channel {
+        if self.balanced.layered.is_some() {
+            tracing::warn!(
+                "the channel-level settings, e.g. the overall timeout, do not apply to the Channel taken out of a LoadBalancedChannel"
+            );
+        }
+
+        self.balanced.channel.clone()
+    }
+
+    /// Replace the tls configuration of the channel, e.g. when a certificate rotates.
+    ///
+    /// The new configuration only applies to the endpoints discovered from now on:
+    /// the connections to the current endpoints keep using the previous configuration
+    /// until they are removed, so the rotation converges as the endpoints churn.
+    /// Setting a configuration on a channel built without tls enables tls for
+    /// the new endpoints.
+    pub fn set_tls_config(&self, tls_config: ClientTlsConfig) {
+        self.state.set_tls_config(tls_config)
+    }
+
+    /// Whether the probe of this channel is currently resolving its service definitions.
+    ///
+    /// A probe that stays in flight for long points at a slow DNS server.
+    pub fn is_probing(&self) -> bool {
+        self.state.is_probing()
+    }
+
+    /// Wait until the first probe of this channel has successfully resolved its endpoints.
+    ///
+    /// Unlike [`ResolutionStrategy::Eager`], failing to resolve does not fail the construction
+    /// of the channel: the returned future completes after the first probe that succeeds,
+    /// whether or not it found any endpoints. It completes immediately if that already happened.
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use std::time::Duration;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    ///
+    ///     tokio::time::timeout(Duration::from_secs(10), load_balanced_channel.first_resolution())
+    ///         .await
+    ///         .expect("timed out waiting for my.hostname to resolve");
+    /// }
+    /// ```
+    pub async fn first_resolution(&self) {
+        self.state.first_resolution().await
+    }
+
+    /// Get a future completing on the first successful resolution, like
+    /// [`first_resolution`](Self::first_resolution), that does not borrow the channel.
+    ///
+    /// With [`ResolutionStrategy::Lazy`], the default, constructing the channel does not wait
+    /// for any resolution: the channel can be handed out right away while the signal is
+    /// awaited separately, e.g. by a readiness check spawned in the background. The signal
+    /// never completes if the channel is dropped before resolving.
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    ///
+    ///     let ready = load_balanced_channel.ready_signal();
+    ///     tokio::spawn(async move {
+    ///         ready.await;
+    ///         println!("my.hostname has been resolved");
+    ///     });
+    /// }
+    /// ```
+    pub fn ready_signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        let state = Arc::clone(&self.state);
+        async move { state.first_resolution().await }
+    }
+
+    /// Get a dedicated [`Channel`] for every endpoint currently known to this channel.
+    ///
+    /// Requests sent through these channels bypass load balancing, which allows callers
+    /// to implement fan-out (e.g. sending an idempotent write to every endpoint) on top of
+    /// the service discovery.
+    ///
+    /// The per-endpoint channels connect lazily and are reused across calls for as long as
+    /// the endpoint is part of the discovered set. This must be called from within a tokio runtime.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use shared_proto::pb::{tester_client::TesterClient, Ping};
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    ///
+    ///     for (address, channel) in load_balanced_channel.per_endpoint_channels() {
+    ///         let response = TesterClient::new(channel).test(Ping {}).await;
+    ///         println!("{}: {:?}", address, response);
+    ///     }
+    /// }
+    /// ```
+    pub fn per_endpoint_channels(&self) -> Vec<(SocketAddr, Channel)> {
+        self.state.endpoint_channels()
+    }
+
+    /// How many endpoints accepted a connection while this channel was constructed with
+    /// [`ResolutionStrategy::EagerConnect`], `None` with any other strategy.
+    pub fn ready_at_startup(&self) -> Option<usize> {
+        self.state.ready_at_startup()
+    }
+
+    /// Get an upper bound of the number of connections held by this channel.
+    ///
+    /// Tonic holds at most one connection to every endpoint it balances over and does not
+    /// report whether that connection has been established yet, so every endpoint counts as
+    /// one connection, plus one for every channel handed out by [`Self::per_endpoint_channels`]
+    /// or used by [`Self::pin_endpoint`].
+    pub fn open_connection_count(&self) -> usize {
+        self.state.open_connection_count()
+    }
+
+    /// How often the service definitions of this channel are resolved,
+    /// see [`LoadBalancedChannelBuilder::dns_probe_interval`].
+    pub fn probe_interval(&self) -> Duration {
+        self.settings.probe_interval
+    }
+
+    /// The timeout of a request as a whole, if any: the overall timeout of the channel,
+    /// or else the timeout applied by every endpoint.
+    ///
+    /// See [`LoadBalancedChannelBuilder::timeout`] and [`LoadBalancedChannelBuilder::overall_timeout`].
+    pub fn timeout(&self) -> Option<Duration> {
+        self.settings.timeout
+    }
+
+    /// The timeout applied to the connection to every endpoint, if any,
+    /// see [`LoadBalancedChannelBuilder::connect_timeout`].
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.settings.connect_timeout
+    }
+
+    /// Whether the endpoints discovered from now on are connected to over tls,
+    /// see [`LoadBalancedChannelBuilder::with_tls`] and [`Self::set_tls_config`].
+    pub fn is_tls_enabled(&self) -> bool {
+        self.state.is_tls_enabled()
+    }
+
+    /// Take a [`DiscoverySnapshot`] of the endpoints currently known to this channel
+    /// and of the outcome of its last probe.
+    pub fn snapshot(&self) -> DiscoverySnapshot {
+        self.state.snapshot()
+    }
+
+    /// Send every request made through this channel and its clones to the endpoint
+    /// at `address`, or restore load balancing with `None`.
+    ///
+    /// This is meant to debug a specific server, e.g. a canary, without changing DNS.
+    /// Pinning fails if `address` is not one of the endpoints currently known to the channel.
+    /// Requests to a pinned endpoint still go through the channel-level layers, but count
+    /// towards a [`global_concurrency_limit`](LoadBalancedChannelBuilder::global_concurrency_limit)
+    /// of their own. If the pinned endpoint stops being discovered, requests fail with an
+    /// `UNAVAILABLE` status until the channel is pinned elsewhere or unpinned.
+    ///
+    /// This must be called from within a tokio runtime.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    ///
+    ///     if let Some(address) = load_balanced_channel.snapshot().endpoints.first() {
+    ///         load_balanced_channel
+    ///             .pin_endpoint(Some(*address))
+    ///             .expect("the endpoint has just been discovered");
+    ///     }
+    /// }
+    /// ```
+    pub fn pin_endpoint(&self, address: Option<SocketAddr>) -> Result<(), anyhow::Error> {
+        let pinned = match address {
+            Some(address) => {
+                let channel = self.state.endpoint_channel(&address).with_context(|| {
+                    format!("{} is not one of the endpoints of the channel", address)
+                })?;
+                Some((address, self.layers.route(channel)))
+            }
+            None => None,
+        };
+
+        *self.pinned.write().expect("pinned endpoint lock poisoned") = pinned;
+
+        Ok(())
+    }
+
+    /// Follow the endpoint the channel is pinned to, if any.
+    fn update_ready_route(&mut self) {
+        let pinned = self.pinned.read().expect("pinned endpoint lock poisoned");
+
+        self.ready = match &*pinned {
+            None => ReadyRoute::Balanced,
+            Some((address, _)) if !self.state.has_endpoint(address) => ReadyRoute::Absent(*address),
+            // Keep the route that may already have been polled for readiness.
+            Some((address, _)) if matches!(self.ready, ReadyRoute::Pinned(ready, _) if ready == *address) => {
+                return
+            }
+            Some((address, route)) => ReadyRoute::Pinned(*address, route.clone()),
+        };
+    }
 }
 
 impl Service<http::Request<BoxBody>> for LoadBalancedChannel {
     type Response = http::Response<<Channel as GrpcService<BoxBody>>::ResponseBody>;
-    type Error = <Channel as GrpcService<BoxBody>>::Error;
-    type Future = <Channel as GrpcService<BoxBody>>::Future;
+    type Error = BoxError;
+    type Future = ResponseFuture;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        GrpcService::poll_ready(&mut self.0, cx)
+        self.update_ready_route();
+
+        match self.ready {
+            ReadyRoute::Balanced => self.balanced.poll_ready(cx),
+            ReadyRoute::Pinned(_, ref mut route) => route.poll_ready(cx),
+            ReadyRoute::Absent(_) => Poll::Ready(Ok(())),
+        }
     }
 
     fn call(&mut self, request: Request<BoxBody>) -> Self::Future {
-        GrpcService::call(&mut self.0, request)
+        let inner = match self.ready {
+            ReadyRoute::Balanced => self.balanced.call(request),
+            ReadyRoute::Pinned(_, ref mut route) => route.call(request),
+            ReadyRoute::Absent(address) => ResponseFutureInner::Failed(Some(
+                Status::unavailable(format!(
+                    "the channel is pinned to {}, which is no longer one of its endpoints",
+                    address
+                ))
+                .into(),
+            )),
+        };
+
+        ResponseFuture {
+            inner,
+            reprobe: self
+                .settings
+                .reprobe_on_unavailable
+                .then(|| Arc::clone(&self.state)),
+        }
+    }
+}
+
+/// Response future of [`LoadBalancedChannel`].
+pub struct ResponseFuture {
+    inner: ResponseFutureInner,
+    /// Set to wake the probe up when the response signals an unavailable endpoint.
+    reprobe: Option<Arc<ProbeState>>,
+}
+
+enum ResponseFutureInner {
+    Channel(<Channel as GrpcService<BoxBody>>::Future),
+    Layered(<LayeredChannel as Service<Request<BoxBody>>>::Future),
+    Failed(Option<BoxError>),
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<http::Response<BoxBody>, BoxError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let result = match self.inner {
+            ResponseFutureInner::Channel(ref mut future) => {
+                ready!(Pin::new(future).poll(cx)).map_err(Into::into)
+            }
+            ResponseFutureInner::Layered(ref mut future) => ready!(Pin::new(future).poll(cx)),
+            ResponseFutureInner::Failed(ref mut err) => {
+                Err(err.take().expect("polled after completion"))
+            }
+        };
+
+        if let Some(state) = &self.reprobe {
+            if signals_unavailable(&result) {
+                state.request_probe();
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+/// Whether `result` suggests that the endpoint that served it is going away, i.e. it
+/// failed below gRPC (e.g. the connection was closed with a `GOAWAY`) or with `UNAVAILABLE`.
+///
+/// Only the status of a trailers-only response is seen, the trailers of a streamed
+/// response arrive after the future has completed.
+fn signals_unavailable(result: &Result<http::Response<BoxBody>, BoxError>) -> bool {
+    match result {
+        Ok(response) => response
+            .headers()
+            .get("grpc-status")
+            .is_some_and(|status| status.as_bytes() == b"14"),
+        // The statuses of the channel-level layers, e.g. timeouts, say nothing about the endpoint.
+        Err(err) => err
+            .downcast_ref::<Status>()
+            .is_none_or(|status| status.code() == tonic::Code::Unavailable),
+    }
+}
+
+impl std::fmt::Debug for ResponseFuture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseFuture").finish()
+    }
+}
+
+/// The channel-level layers, kept to wrap the channel of a pinned endpoint
+/// like the balanced one.
+#[derive(Clone)]
+struct ChannelLayers {
+    channel_layer: Option<ChannelLayerFn>,
+    overall_timeout: Option<Duration>,
+    global_concurrency_limit: Option<usize>,
+    default_headers: Option<http::HeaderMap>,
+}
+
+impl ChannelLayers {
+    /// Wrap `channel` with the channel-level layers, if any is configured.
+    ///
+    /// The layer provided with [`LoadBalancedChannelBuilder::with_channel_layer`] is the innermost,
+    /// so that the overall timeout and concurrency limit bound everything it does (e.g. retries).
+    fn route(&self, channel: Channel) -> Route {
+        if self.channel_layer.is_none()
+            && self.overall_timeout.is_none()
+            && self.global_concurrency_limit.is_none()
+            && self.default_headers.is_none()
+        {
+            return Route {
+                channel,
+                layered: None,
+            };
+        }
+
+        let inner = match &self.channel_layer {
+            Some(channel_layer) => channel_layer(channel.clone()),
+            None => BoxService::new(channel.clone().map_err(BoxError::from)),
+        };
+
+        let service = ServiceBuilder::new()
+            .map_err(channel_layer_error)
+            .option_layer(self.overall_timeout.map(tower::timeout::TimeoutLayer::new))
+            .option_layer(self.global_concurrency_limit.map(|limit| {
+                ServiceBuilder::new()
+                    .load_shed()
+                    .concurrency_limit(limit)
+                    .into_inner()
+            }))
+            .option_layer(self.default_headers.clone().map(|default_headers| {
+                tower::util::MapRequestLayer::new(move |request| {
+                    with_default_headers(request, &default_headers)
+                })
+            }))
+            .service(inner)
+            .boxed();
+
+        Route {
+            channel,
+            layered: Some(Buffer::new(service, LAYERED_CHANNEL_BUFFER_SIZE)),
+        }
+    }
+}
+
+/// Add the `default_headers` that are not already set to `request`.
+fn with_default_headers(
+    mut request: Request<BoxBody>,
+    default_headers: &http::HeaderMap,
+) -> Request<BoxBody> {
+    for name in default_headers.keys() {
+        if !request.headers().contains_key(name) {
+            for value in default_headers.get_all(name) {
+                request.headers_mut().append(name, value.clone());
+            }
+        }
+    }
+
+    request
+}
+
+/// Convert the errors of the channel-level layers to a [`Status`],
+/// so that the generated clients report them with the right code.
+fn channel_layer_error(err: BoxError) -> BoxError {
+    if err.is::<Elapsed>() {
+        return Status::deadline_exceeded("request timed out").into();
+    }
+    if err.is::<Overloaded>() {
+        return Status::resource_exhausted("too many requests in flight on the channel").into();
     }
+
+    err
 }
 
 /// Enumerates the different domain name resolution strategies that
@@ -92,15 +704,174 @@ pub enum ResolutionStrategy {
     /// Tries to resolve the domain name before creating the channel
     /// in order to start with a non-empty set of IPs.
     Eager { timeout: Duration },
+    /// Resolves the domain name as [`ResolutionStrategy::Eager`] does, then connects to
+    /// every resolved endpoint and fails unless at least `min_ready` of them accept
+    /// a connection, all within `timeout`.
+    ///
+    /// The number of endpoints that connected is available from
+    /// [`LoadBalancedChannel::ready_at_startup`]. Those connections only validate that the
+    /// endpoints are reachable: the channel opens its own connections as it is called.
+    EagerConnect { timeout: Duration, min_ready: usize },
+    /// Resolves the domain name as [`ResolutionStrategy::Eager`] does, then never again.
+    ///
+    /// No probe keeps running in the background, which spares the periodic lookups for
+    /// services whose endpoints do not change during the lifetime of the process,
+    /// e.g. a fixed set of addresses behind a stable virtual IP. The endpoints, and the
+    /// TLS configuration they were reported with, stay as resolved at startup.
+    Once { timeout: Duration },
+}
+
+/// Errors returned when a [`LoadBalancedChannel`] is constructed with
+/// [`ResolutionStrategy::Eager`] or [`ResolutionStrategy::EagerConnect`]
+/// and the initial resolution is not acceptable.
+///
+/// The error is wrapped in the [`anyhow::Error`] returned by
+/// [`LoadBalancedChannelBuilder::channel`] and can be retrieved with `downcast_ref`.
+#[derive(thiserror::Error, Debug)]
+pub enum ResolutionError {
+    /// The resolution succeeded without finding any endpoint, while
+    /// [`LoadBalancedChannelBuilder::fail_on_empty_resolution`] is set.
+    #[error("resolved an empty set of endpoints")]
+    NoEndpoints,
+    /// Fewer than `required` endpoints accepted a connection with
+    /// [`ResolutionStrategy::EagerConnect`].
+    #[error("{ready} endpoints accepted a connection while {required} are required")]
+    NotEnoughReadyEndpoints { ready: usize, required: usize },
+}
+
+/// Enumerates the orders in which the endpoint changes discovered by a single probe
+/// can be reported to the load balancer.
+///
+/// In deployments with high churn a probe can discover many new endpoints while many old ones
+/// disappear. The order in which those changes are reported determines what the balancer
+/// can pick from while the changeset is being applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChangeOrdering {
+    /// Report all the new endpoints before removing the old ones.
+    ///
+    /// The set of endpoints never drops to zero while a full replacement is being applied,
+    /// at the cost of briefly routing to endpoints that are about to be removed.
+    #[default]
+    InsertsFirst,
+    /// Remove all the old endpoints before reporting the new ones.
+    ///
+    /// Stale endpoints stop receiving traffic as soon as possible, at the cost of a window
+    /// where a full replacement leaves the balancer with no endpoints at all.
+    RemovesFirst,
+    /// Alternate between reporting a new endpoint and removing an old one.
+    ///
+    /// Keeps the size of the set stable while a changeset is being applied, which spreads
+    /// the connection churn, but neither avoids routing to stale endpoints nor fully
+    /// protects against a momentarily thin set.
+    Interleaved,
+}
+
+/// Enumerates the ways a probe handles resolved addresses that can not be turned
+/// into an [`Endpoint`](tonic::transport::Endpoint), e.g. because the TLS configuration
+/// can not be applied to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialResultPolicy {
+    /// Log and skip the addresses that can not be turned into an endpoint,
+    /// and report the other ones. The skipped addresses are tried again by the next probe.
+    #[default]
+    Ignore,
+    /// Fail the whole probe, leaving the current endpoints untouched, so that a
+    /// misconfiguration is surfaced rather than silently shrinking the set of endpoints.
+    ///
+    /// With [`ResolutionStrategy::Eager`] the construction of the channel fails.
+    FailProbe,
+}
+
+/// Enumerates the ways a probe handles a full buffer of changes, i.e. when tonic
+/// applies the reported changes slower than the probe discovers them.
+///
+/// Tonic only applies the changes when the channel is called, so a large changeset
+/// reported to a channel that is not being called fills the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullBufferPolicy {
+    /// Wait until tonic makes room for every change of the changeset,
+    /// delaying the following probes until then.
+    #[default]
+    Block,
+    /// Stop reporting the changeset and leave the changes that did not fit
+    /// to the next probe, so that a slow consumer never stalls the probe.
+    ///
+    /// The changes reported before the buffer filled up are kept, and the endpoints
+    /// converge over the following probes.
+    SkipChangeset,
+}
+
+/// The smallest difference between the resolved endpoints and the ones reported to tonic
+/// for a probe to report it, set with [`LoadBalancedChannelBuilder::change_report_threshold`].
+///
+/// The difference counts the endpoints to add and the endpoints to remove.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeReportThreshold {
+    /// At least this many endpoints differ.
+    Count(usize),
+    /// At least this fraction of the endpoints reported to tonic differ, e.g. `0.05` for 5%.
+    Fraction(f64),
+}
+
+impl ChangeReportThreshold {
+    /// Whether `difference` changed endpoints out of `reported` ones reach the threshold.
+    pub(crate) fn is_reached(&self, difference: usize, reported: usize) -> bool {
+        match *self {
+            ChangeReportThreshold::Count(count) => difference >= count,
+            ChangeReportThreshold::Fraction(fraction) => {
+                difference as f64 >= fraction * reported as f64
+            }
+        }
+    }
 }
 
 /// Builder to configure and create a [`LoadBalancedChannel`].
 pub struct LoadBalancedChannelBuilder<T, S> {
     service_definition: S,
+    name: Option<String>,
     probe_interval: Option<Duration>,
     resolution_strategy: ResolutionStrategy,
+    change_ordering: ChangeOrdering,
+    partial_result_policy: PartialResultPolicy,
+    full_buffer_policy: FullBufferPolicy,
+    tcp_precheck: bool,
+    grpc_precheck: bool,
+    probe_observer: Option<Arc<dyn ProbeObserver>>,
+    snapshot_log_interval: Option<u32>,
+    on_endpoints_available: Option<EndpointsCallback>,
+    on_endpoints_exhausted: Option<EndpointsCallback>,
+    seed_endpoints: HashSet<SocketAddr>,
+    fail_on_empty_resolution: bool,
+    eager_retry_delay: Option<Duration>,
+    balancing_seed: Option<u64>,
+    dns_record_kind: DnsRecordKind,
+    mesh_passthrough: bool,
+    change_report_threshold: Option<ChangeReportThreshold>,
+    eject_unconnectable_after: Option<u32>,
+    default_headers: Option<http::HeaderMap>,
+    adaptive_interval: Option<(Duration, Duration)>,
+    endpoint_uri_builder: Option<EndpointUriFn>,
+    reprobe_on_unavailable: bool,
+    initial_probe_jitter: Option<Duration>,
+    tls_domain_name: Option<String>,
+    max_endpoints_hard_limit: Option<usize>,
+    log_probe_errors: bool,
+    on_consecutive_failures: Option<(u32, FailuresCallback)>,
+    zero_downtime_cutover: bool,
+    warmup_concurrency: Option<usize>,
+    max_open_connections: Option<usize>,
+    override_tls_domain_name: bool,
+    removal_confirmations: u32,
+    min_healthy_warn: Option<usize>,
+    address_rewrite: Option<AddressRewriteFn>,
     timeout: Option<Duration>,
+    timeout_per_try: Option<Duration>,
+    overall_timeout: Option<Duration>,
+    global_concurrency_limit: Option<usize>,
+    channel_layer: Option<ChannelLayerFn>,
     connect_timeout: Option<Duration>,
+    http2_adaptive_window: Option<bool>,
+    endpoint_layer: Option<EndpointLayerFn>,
     tls_config: Option<ClientTlsConfig>,
     lookup_service: Option<T>,
 }
@@ -119,12 +890,88 @@ where
     pub fn new_with_service(service_definition: S) -> LoadBalancedChannelBuilder<DnsResolver, S> {
         Self {
             service_definition,
+            name: None,
             probe_interval: None,
             timeout: None,
+            timeout_per_try: None,
+            overall_timeout: None,
+            global_concurrency_limit: None,
+            channel_layer: None,
             connect_timeout: None,
+            http2_adaptive_window: None,
+            endpoint_layer: None,
             tls_config: None,
             lookup_service: None,
             resolution_strategy: ResolutionStrategy::Lazy,
+            change_ordering: ChangeOrdering::default(),
+            partial_result_policy: PartialResultPolicy::default(),
+            full_buffer_policy: FullBufferPolicy::default(),
+            tcp_precheck: false,
+            grpc_precheck: false,
+            probe_observer: None,
+            snapshot_log_interval: None,
+            on_endpoints_available: None,
+            on_endpoints_exhausted: None,
+            seed_endpoints: HashSet::new(),
+            fail_on_empty_resolution: false,
+            eager_retry_delay: None,
+            balancing_seed: None,
+            dns_record_kind: DnsRecordKind::default(),
+            mesh_passthrough: false,
+            change_report_threshold: None,
+            eject_unconnectable_after: None,
+            default_headers: None,
+            adaptive_interval: None,
+            endpoint_uri_builder: None,
+            reprobe_on_unavailable: false,
+            initial_probe_jitter: None,
+            tls_domain_name: None,
+            max_endpoints_hard_limit: None,
+            log_probe_errors: true,
+            on_consecutive_failures: None,
+            zero_downtime_cutover: false,
+            warmup_concurrency: None,
+            max_open_connections: None,
+            override_tls_domain_name: true,
+            removal_confirmations: 1,
+            min_healthy_warn: None,
+            address_rewrite: None,
+        }
+    }
+
+    /// Set the kind of DNS records queried to resolve the service, [`DnsRecordKind::Ip`]
+    /// by default. With [`DnsRecordKind::Srv`] the port of the [`ServiceDefinition`] is
+    /// ignored in favour of the ports of the `SRV` records.
+    ///
+    /// This configures the [`DnsResolver`] read from the system configuration: a resolver
+    /// set with [`LoadBalancedChannelBuilder::lookup_service`] keeps its own record kind,
+    /// see [`DnsResolver::with_record_kind`].
+    ///
+    /// With TLS, the certificates are verified against the hostname by default, i.e. the
+    /// name of the `SRV` records which no certificate is issued for: set
+    /// [`LoadBalancedChannelBuilder::tls_domain_name`] along with the `SRV` record kinds.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::{DnsRecordKind, LoadBalancedChannel};
+    ///
+    ///     // 5000 is ignored, the ports of the SRV records are used instead.
+    ///     let load_balanced_channel =
+    ///         LoadBalancedChannel::builder(("_grpc._tcp.my.hostname", 5000))
+    ///             .dns_record_kind(DnsRecordKind::Srv)
+    ///             .channel()
+    ///             .await
+    ///             .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn dns_record_kind(
+        self,
+        record_kind: DnsRecordKind,
+    ) -> LoadBalancedChannelBuilder<DnsResolver, S> {
+        Self {
+            dns_record_kind: record_kind,
+            ..self
         }
     }
 
@@ -136,11 +983,51 @@ where
         LoadBalancedChannelBuilder {
             lookup_service: Some(lookup_service),
             service_definition: self.service_definition,
+            name: self.name,
             probe_interval: self.probe_interval,
             tls_config: self.tls_config,
             timeout: self.timeout,
+            timeout_per_try: self.timeout_per_try,
+            overall_timeout: self.overall_timeout,
+            global_concurrency_limit: self.global_concurrency_limit,
+            channel_layer: self.channel_layer,
             connect_timeout: self.connect_timeout,
+            http2_adaptive_window: self.http2_adaptive_window,
+            endpoint_layer: self.endpoint_layer,
             resolution_strategy: self.resolution_strategy,
+            change_ordering: self.change_ordering,
+            partial_result_policy: self.partial_result_policy,
+            full_buffer_policy: self.full_buffer_policy,
+            tcp_precheck: self.tcp_precheck,
+            grpc_precheck: self.grpc_precheck,
+            probe_observer: self.probe_observer,
+            snapshot_log_interval: self.snapshot_log_interval,
+            on_endpoints_available: self.on_endpoints_available,
+            on_endpoints_exhausted: self.on_endpoints_exhausted,
+            seed_endpoints: self.seed_endpoints,
+            fail_on_empty_resolution: self.fail_on_empty_resolution,
+            eager_retry_delay: self.eager_retry_delay,
+            balancing_seed: self.balancing_seed,
+            dns_record_kind: self.dns_record_kind,
+            mesh_passthrough: self.mesh_passthrough,
+            change_report_threshold: self.change_report_threshold,
+            eject_unconnectable_after: self.eject_unconnectable_after,
+            default_headers: self.default_headers,
+            adaptive_interval: self.adaptive_interval,
+            endpoint_uri_builder: self.endpoint_uri_builder,
+            reprobe_on_unavailable: self.reprobe_on_unavailable,
+            initial_probe_jitter: self.initial_probe_jitter,
+            tls_domain_name: self.tls_domain_name,
+            max_endpoints_hard_limit: self.max_endpoints_hard_limit,
+            log_probe_errors: self.log_probe_errors,
+            on_consecutive_failures: self.on_consecutive_failures,
+            zero_downtime_cutover: self.zero_downtime_cutover,
+            warmup_concurrency: self.warmup_concurrency,
+            max_open_connections: self.max_open_connections,
+            override_tls_domain_name: self.override_tls_domain_name,
+            removal_confirmations: self.removal_confirmations,
+            min_healthy_warn: self.min_healthy_warn,
+            address_rewrite: self.address_rewrite,
         }
     }
 }
@@ -150,8 +1037,26 @@ where
     S: TryInto<ServiceDefinition> + 'static,
     S::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send + Sync,
 {
+    /// Set a name identifying the channel in the logs, e.g. when several channels
+    /// resolve the same hostname.
+    ///
+    /// The probe of the channel runs within a `ginepro_probe` span, recording the name
+    /// in its `channel_name` field alongside the `hostname` of the service definition.
+    pub fn name(self, name: impl Into<String>) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
     /// Set the how often, the client should probe for changes to  gRPC server endpoints.
     /// Default interval in seconds is 10.
+    ///
+    /// The probe only waits on the tokio clock, so tests can step through probe cycles
+    /// instantly with `tokio::time::pause` and `tokio::time::advance`.
+    ///
+    /// A [`LookupService`] can hint a different delay until the next probe with
+    /// [`LookupService::resolve_service_endpoints_with_metadata`]; the interval applies otherwise.
     pub fn dns_probe_interval(self, interval: Duration) -> LoadBalancedChannelBuilder<T, S> {
         Self {
             probe_interval: Some(interval),
@@ -159,114 +1064,1247 @@ where
         }
     }
 
-    /// Set a request timeout that will be applied to every new `Endpoint`.
-    pub fn timeout(self, timeout: Duration) -> LoadBalancedChannelBuilder<T, S> {
+    /// Probe right away, rather than at the end of the probe interval, when a call fails
+    /// with `UNAVAILABLE` or below gRPC, e.g. because a draining server closed its
+    /// connections with a `GOAWAY`.
+    ///
+    /// Tonic does not tell which endpoint served a call, so the endpoint is not ejected
+    /// directly: it is removed as soon as the lookup service stops returning it, which
+    /// narrows the window during which a draining server keeps being routed to. Early
+    /// probes are at least a second apart, or the probe interval if it is shorter.
+    ///
+    /// Disabled by default.
+    pub fn reprobe_on_unavailable(self, enabled: bool) -> LoadBalancedChannelBuilder<T, S> {
         Self {
-            timeout: Some(timeout),
+            reprobe_on_unavailable: enabled,
             ..self
         }
     }
 
-    /// Set a connection timeout that will be applied to every new `Endpoint`.
+    /// Adapt the interval between probes to the churn of the endpoints, between `min`
+    /// and `max`: it drops to `min` as soon as a probe finds a change, and doubles after
+    /// every probe that finds none, up to `max`. Failed probes keep the current interval.
     ///
-    /// Defaults to the overall request `timeout` if not set.
-    pub fn connect_timeout(self, connection_timeout: Duration) -> LoadBalancedChannelBuilder<T, S> {
+    /// This suits services that are mostly stable but occasionally churn, which a fixed
+    /// interval either probes too often or notices too late. The probing starts from the
+    /// [`dns_probe_interval`](Self::dns_probe_interval), clamped between `min` and `max`,
+    /// and the delays hinted by the [`LookupService`] still take precedence.
+    pub fn adaptive_interval(
+        self,
+        min: Duration,
+        max: Duration,
+    ) -> LoadBalancedChannelBuilder<T, S> {
         Self {
-            connect_timeout: Some(connection_timeout),
+            adaptive_interval: Some((min, max.max(min))),
             ..self
         }
     }
 
-    /// Set the [`ResolutionStrategy`].
-    ///
-    /// Default set to [`ResolutionStrategy::Lazy`].
+    /// Delay the first probe of the background loop by a random offset of up to `max`,
+    /// so that the channels an application creates together at startup do not all hit
+    /// the name server at the same instant. The following probes keep their interval.
     ///
-    /// If [`ResolutionStrategy::Lazy`] the domain name will be resolved after-the-fact.
+    /// The resolution of an eager [`ResolutionStrategy`] is not delayed, only the
+    /// probes that follow it. Disabled by default.
+    pub fn initial_probe_jitter(self, max: Duration) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            initial_probe_jitter: Some(max),
+            ..self
+        }
+    }
+
+    /// Derive the pseudo-random choices of the channel from `seed`, so that they are the
+    /// same across restarts and test runs: the offset of
+    /// [`LoadBalancedChannelBuilder::initial_probe_jitter`] is then derived from the seed
+    /// and the service definition, so that the channels sharing a seed are still spread.
     ///
-    /// Instead, if [`ResolutionStrategy::Eager`] is set the domain name will be attempted resolved
-    /// once before the [`LoadBalancedChannel`] is created, which ensures that the channel
-    /// will have a non-empty of IPs on startup. If it fails the channel creation will also fail.
-    pub fn resolution_strategy(
-        self,
-        resolution_strategy: ResolutionStrategy,
-    ) -> LoadBalancedChannelBuilder<T, S> {
+    /// The endpoint every request is sent to is picked by tonic's balancer, whose
+    /// randomness can not be seeded. The choices are random by default.
+    pub fn balancing_seed(self, seed: u64) -> LoadBalancedChannelBuilder<T, S> {
         Self {
-            resolution_strategy,
+            balancing_seed: Some(seed),
             ..self
         }
     }
 
-    /// Configure the channel to use tls.
-    /// A `tls_config` MUST be specified to use the `HTTPS` scheme.
-    pub fn with_tls(self, tls_config: ClientTlsConfig) -> LoadBalancedChannelBuilder<T, S> {
+    /// Set a request timeout that will be applied to every new `Endpoint`.
+    pub fn timeout(self, timeout: Duration) -> LoadBalancedChannelBuilder<T, S> {
         Self {
-            tls_config: Some(tls_config),
+            timeout: Some(timeout),
             ..self
         }
     }
 
-    /// Construct a [`LoadBalancedChannel`] from the [`LoadBalancedChannelBuilder`] instance.
-    pub async fn channel(mut self) -> Result<LoadBalancedChannel, anyhow::Error> {
-        match self.lookup_service.take() {
-            Some(lookup_service) => self.channel_inner(lookup_service).await,
-            None => {
-                self.channel_inner(DnsResolver::from_system_config().await?)
-                    .await
-            }
+    /// Set a timeout for every single attempt of a request, applied to every new `Endpoint`.
+    ///
+    /// When set, the request `timeout` is no longer applied to the endpoints but to the channel
+    /// as a whole, where it bounds the total time of a request across all of its attempts
+    /// (e.g. when retries are layered on top of the balanced channel).
+    /// Without retries there is a single attempt, so the request fails after the shorter of
+    /// the two timeouts.
+    ///
+    /// If not set, `timeout` is applied to every new `Endpoint` as before.
+    pub fn timeout_per_try(self, timeout_per_try: Duration) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            timeout_per_try: Some(timeout_per_try),
+            ..self
         }
     }
 
-    async fn channel_inner<U>(self, lookup_service: U) -> Result<LoadBalancedChannel, anyhow::Error>
+    /// Set a deadline for the whole of every request made through the [`LoadBalancedChannel`],
+    /// applied to the channel as a whole rather than to the endpoints.
+    ///
+    /// It bounds everything that happens once the request has been sent through the channel:
+    /// waiting for a ready endpoint, connecting to it, and the attempts made by a layer set
+    /// with [`with_channel_layer`](LoadBalancedChannelBuilder::with_channel_layer), e.g. retries.
+    /// Requests that run out of time fail with a `DEADLINE_EXCEEDED` status.
+    ///
+    /// It composes with the endpoint timeouts rather than replacing them: `timeout`
+    /// (or `timeout_per_try` if set) still bounds every single attempt, and a request fails
+    /// after whichever of the two elapses first.
+    /// When set, it takes precedence over `timeout` as the overall deadline of a request
+    /// when [`timeout_per_try`](LoadBalancedChannelBuilder::timeout_per_try) is also set.
+    ///
+    /// Like all the channel-level layers, it is bypassed by the [`Channel`] obtained from
+    /// [`LoadBalancedChannel::channel`] or through `From`.
+    pub fn overall_timeout(self, overall_timeout: Duration) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            overall_timeout: Some(overall_timeout),
+            ..self
+        }
+    }
+
+    /// Limit the number of requests in flight across the whole channel.
+    ///
+    /// Requests made while `limit` requests are already in flight are rejected immediately
+    /// with a `RESOURCE_EXHAUSTED` status, protecting the resources of the client itself.
+    /// The limit applies to the channel and all its clones, regardless of how requests are
+    /// spread across endpoints.
+    pub fn global_concurrency_limit(self, limit: usize) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            global_concurrency_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Add `headers` to every request made through the [`LoadBalancedChannel`], e.g. the
+    /// static routing header a gateway requires. The headers a request already carries are
+    /// left untouched, so a call can still override a default header through its metadata.
+    ///
+    /// Like all the channel-level layers, it is bypassed by the [`Channel`] obtained from
+    /// [`LoadBalancedChannel::channel`] or through `From`.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let mut headers = http::HeaderMap::new();
+    ///     headers.insert("x-route", http::HeaderValue::from_static("payments"));
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .default_headers(headers)
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn default_headers(self, headers: http::HeaderMap) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            default_headers: Some(headers),
+            ..self
+        }
+    }
+
+    /// Wrap the balanced [`Channel`] with a tower [`Layer`], e.g. to add authentication,
+    /// logging or retries to every request made through the [`LoadBalancedChannel`].
+    ///
+    /// The layer wraps the channel as a whole, after load balancing has been set up, and
+    /// replaces any layer set previously; compose several layers with [`ServiceBuilder`].
+    /// It sits beneath the layers configured by ginepro itself, so the
+    /// [`overall_timeout`](LoadBalancedChannelBuilder::overall_timeout) and the
+    /// [`global_concurrency_limit`](LoadBalancedChannelBuilder::global_concurrency_limit)
+    /// bound everything the layer does.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use std::time::Duration;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .with_channel_layer(tower::ServiceBuilder::new().map_request(
+    ///             |request: http::Request<tonic::body::BoxBody>| {
+    ///                 tracing::info!("calling {}", request.uri());
+    ///                 request
+    ///             },
+    ///         ))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn with_channel_layer<L>(self, layer: L) -> LoadBalancedChannelBuilder<T, S>
+    where
+        L: Layer<Channel> + Send + 'static,
+        L::Service: Service<Request<BoxBody>, Response = http::Response<BoxBody>> + Send + 'static,
+        <L::Service as Service<Request<BoxBody>>>::Error: Into<BoxError>,
+        <L::Service as Service<Request<BoxBody>>>::Future: Send + 'static,
+    {
+        // The layer is applied again to the channel of a pinned endpoint,
+        // and only needs to be `Send` to be shared behind a mutex.
+        let layer = std::sync::Mutex::new(layer);
+        Self {
+            channel_layer: Some(Arc::new(move |channel| {
+                let layer = layer.lock().expect("channel layer lock poisoned");
+                BoxService::new(layer.layer(channel).map_err(Into::into))
+            })),
+            ..self
+        }
+    }
+
+    /// Set a connection timeout that will be applied to every new `Endpoint`.
+    ///
+    /// Defaults to the overall request `timeout` if not set.
+    pub fn connect_timeout(self, connection_timeout: Duration) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            connect_timeout: Some(connection_timeout),
+            ..self
+        }
+    }
+
+    /// Enable or disable HTTP/2 adaptive flow control on every new `Endpoint`.
+    ///
+    /// Adaptive flow control grows the HTTP/2 windows with the measured bandwidth-delay
+    /// product, which improves the throughput of large streaming messages.
+    /// Defaults to tonic's setting, i.e. disabled.
+    pub fn http2_adaptive_window(self, enabled: bool) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            http2_adaptive_window: Some(enabled),
+            ..self
+        }
+    }
+
+    /// Customize every `Endpoint` built by the probe, e.g. to set a user agent.
+    ///
+    /// `layer` is applied after the settings of the builder, so it can override them.
+    /// It replaces the layer set with [`Self::with_endpoint_layer_for_addr`], if any.
+    /// An error fails to build the endpoint: it is logged with the address of the endpoint
+    /// and handled according to the [`PartialResultPolicy`], rather than silently dropping
+    /// the endpoint.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .try_with_endpoint_layer(|endpoint| endpoint.user_agent("my-service/1.0"))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn try_with_endpoint_layer<F, E>(self, layer: F) -> LoadBalancedChannelBuilder<T, S>
+    where
+        F: Fn(Endpoint) -> Result<Endpoint, E> + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self {
+            endpoint_layer: Some(Box::new(move |_, endpoint| {
+                layer(endpoint)
+                    .map_err(Into::into)
+                    .map_err(|err| anyhow::anyhow!(err))
+            })),
+            ..self
+        }
+    }
+
+    /// Customize every `Endpoint` built by the probe depending on its address, e.g. to give
+    /// the endpoints of a known-slow subnet a longer timeout.
+    ///
+    /// `layer` is applied as the one of [`Self::try_with_endpoint_layer`], which it replaces.
+    /// Returning `None` rejects the address: it is logged and handled according to the
+    /// [`PartialResultPolicy`], as an endpoint that fails to build.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use std::net::IpAddr;
+    ///     use std::time::Duration;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .with_endpoint_layer_for_addr(|address, endpoint| match address.ip() {
+    ///             IpAddr::V4(ip) if ip.octets()[..3] == [10, 0, 42] => {
+    ///                 Some(endpoint.timeout(Duration::from_secs(5)))
+    ///             }
+    ///             _ => Some(endpoint),
+    ///         })
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn with_endpoint_layer_for_addr<F>(self, layer: F) -> LoadBalancedChannelBuilder<T, S>
+    where
+        F: Fn(SocketAddr, Endpoint) -> Option<Endpoint> + Send + Sync + 'static,
+    {
+        Self {
+            endpoint_layer: Some(Box::new(move |address, endpoint| {
+                layer(address, endpoint)
+                    .ok_or_else(|| anyhow::anyhow!("the endpoint layer rejected {}", address))
+            })),
+            ..self
+        }
+    }
+
+    /// Set the [`ResolutionStrategy`].
+    ///
+    /// Default set to [`ResolutionStrategy::Lazy`].
+    ///
+    /// If [`ResolutionStrategy::Lazy`] the domain name will be resolved after-the-fact.
+    ///
+    /// Instead, if [`ResolutionStrategy::Eager`] is set the domain name will be attempted resolved
+    /// once before the [`LoadBalancedChannel`] is created, which ensures that the channel
+    /// will have a non-empty of IPs on startup. If it fails the channel creation will also fail.
+    /// A resolution that succeeds without any IP is accepted, unless
+    /// [`LoadBalancedChannelBuilder::fail_on_empty_resolution`] is set.
+    ///
+    /// With [`ResolutionStrategy::Once`] the domain name is resolved as with
+    /// [`ResolutionStrategy::Eager`], and the endpoints are never probed again.
+    pub fn resolution_strategy(
+        self,
+        resolution_strategy: ResolutionStrategy,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            resolution_strategy,
+            ..self
+        }
+    }
+
+    /// Fail the construction of the channel when the [`ResolutionStrategy::Eager`]
+    /// resolution succeeds without finding any endpoint.
+    ///
+    /// By default an empty resolution is accepted and the channel is constructed without
+    /// endpoints, to be populated by the following probes.
+    /// When set, [`LoadBalancedChannelBuilder::channel`] fails with
+    /// [`ResolutionError::NoEndpoints`] instead.
+    /// This has no effect with [`ResolutionStrategy::Lazy`].
+    pub fn fail_on_empty_resolution(
+        self,
+        fail_on_empty_resolution: bool,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            fail_on_empty_resolution,
+            ..self
+        }
+    }
+
+    /// Retry the [`ResolutionStrategy::Eager`] resolution until it finds endpoints,
+    /// waiting `initial_delay` before the first retry and doubling the delay after each one.
+    ///
+    /// Failed and empty resolutions are retried as long as the next attempt starts before the
+    /// eager resolution `timeout` elapses. The outcome of the last attempt is then handled as
+    /// it would be without retries: an error fails the construction of the channel,
+    /// while an empty resolution only does if
+    /// [`LoadBalancedChannelBuilder::fail_on_empty_resolution`] is set.
+    /// This smooths over DNS records that take a few seconds to be populated,
+    /// e.g. during coordinated restarts.
+    ///
+    /// By default the eager resolution is attempted once.
+    /// This has no effect with [`ResolutionStrategy::Lazy`].
+    pub fn eager_retry_backoff(self, initial_delay: Duration) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            eager_retry_delay: Some(initial_delay),
+            ..self
+        }
+    }
+
+    /// Set the [`ChangeOrdering`] used to report the endpoint changes discovered by a probe.
+    ///
+    /// Default set to [`ChangeOrdering::InsertsFirst`], which avoids a gap where zero endpoints
+    /// are available.
+    pub fn change_ordering(
+        self,
+        change_ordering: ChangeOrdering,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            change_ordering,
+            ..self
+        }
+    }
+
+    /// Set the [`PartialResultPolicy`] applied when some of the resolved addresses
+    /// can not be turned into an endpoint.
+    ///
+    /// Default set to [`PartialResultPolicy::Ignore`].
+    pub fn partial_result_policy(
+        self,
+        partial_result_policy: PartialResultPolicy,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            partial_result_policy,
+            ..self
+        }
+    }
+
+    /// Connect to the hostname and port of the service definition as they are, rather than
+    /// resolving it and balancing over the resolved addresses, for deployments into a
+    /// service mesh (e.g. Istio or Linkerd) whose sidecar intercepts the connections to
+    /// the service name or virtual ip and balances them itself.
+    ///
+    /// When enabled, no probe is started: the channel holds a single lazy connection to the
+    /// hostname, which is resolved by the system resolver whenever the connection is
+    /// established. The channel reports no endpoints and counts as resolved right away.
+    ///
+    /// Only these settings apply to the connection:
+    /// * the TLS configuration: [`LoadBalancedChannelBuilder::with_tls`],
+    ///   [`LoadBalancedChannelBuilder::tls_domain_name`] and
+    ///   [`LoadBalancedChannelBuilder::override_tls_domain_name`];
+    /// * the timeouts: [`LoadBalancedChannelBuilder::timeout`],
+    ///   [`LoadBalancedChannelBuilder::timeout_per_try`],
+    ///   [`LoadBalancedChannelBuilder::overall_timeout`] and
+    ///   [`LoadBalancedChannelBuilder::connect_timeout`];
+    /// * [`LoadBalancedChannelBuilder::http2_adaptive_window`];
+    /// * the channel-level settings: [`LoadBalancedChannelBuilder::with_channel_layer`],
+    ///   [`LoadBalancedChannelBuilder::global_concurrency_limit`] and
+    ///   [`LoadBalancedChannelBuilder::default_headers`].
+    ///
+    /// Every other setting is ignored, as it configures the discovery of the endpoints or
+    /// works on resolved addresses: the [`LookupService`] (a warning is logged if one is
+    /// set), the probe and its interval, the [`ResolutionStrategy`] and
+    /// [`LoadBalancedChannelBuilder::fail_on_empty_resolution`], the seed endpoints,
+    /// the prechecks, the callbacks and the [`ProbeObserver`], and the endpoint layers.
+    ///
+    /// The same code can then balance on its own outside of a mesh, and defer to the mesh
+    /// inside one, depending on this flag. Disabled by default.
+    pub fn mesh_passthrough(self, enabled: bool) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            mesh_passthrough: enabled,
+            ..self
+        }
+    }
+
+    /// Only report the changes of the endpoints to tonic once they add up to `threshold`,
+    /// rather than on every probe that finds any.
+    ///
+    /// The difference accumulates across probes, since it is always measured against the
+    /// endpoints reported last, and is reported in one go once it reaches the threshold.
+    /// This trades freshness for less rebalancing in huge pools whose endpoints constantly
+    /// jitter: a small change, such as a single endpoint going away, can wait indefinitely
+    /// if nothing else changes. The first endpoints, and a resolution leaving none, are
+    /// always reported.
+    ///
+    /// Unset by default, reporting every change.
+    pub fn change_report_threshold(
+        self,
+        threshold: ChangeReportThreshold,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            change_report_threshold: Some(threshold),
+            ..self
+        }
+    }
+
+    /// Set the [`FullBufferPolicy`] applied when the buffer of changes waiting to be
+    /// applied by tonic is full.
+    ///
+    /// Default set to [`FullBufferPolicy::Block`].
+    pub fn full_buffer_policy(
+        self,
+        full_buffer_policy: FullBufferPolicy,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            full_buffer_policy,
+            ..self
+        }
+    }
+
+    /// Only report newly discovered endpoints that accept a TCP connection.
+    ///
+    /// When enabled, the probe attempts a TCP connect to every new address, bounded by the
+    /// `connect_timeout`, and skips the ones that cannot be reached. Skipped addresses are
+    /// checked again on the next probe.
+    /// This keeps endpoints without a backing server out of the balancer, at the cost of
+    /// adding the connect latency to the discovery of new endpoints.
+    ///
+    /// Disabled by default.
+    pub fn tcp_precheck(self, tcp_precheck: bool) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            tcp_precheck,
+            ..self
+        }
+    }
+
+    /// Only report newly discovered endpoints that answer a gRPC request with a gRPC response.
+    ///
+    /// When enabled, the probe sends a `grpc.health.v1.Health/Check` request to every new
+    /// address, bounded by the `connect_timeout`, and skips the ones that do not respond with
+    /// gRPC, logging a diagnostic. Any gRPC status is accepted, so servers without a health
+    /// service pass the check. Skipped addresses are checked again on the next probe.
+    /// This catches a service definition pointing at a non-gRPC port (e.g. a plain HTTP one)
+    /// early, rather than through cryptic stream errors, at the cost of an extra connection
+    /// and request for every new endpoint.
+    ///
+    /// Disabled by default.
+    pub fn grpc_precheck(self, grpc_precheck: bool) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            grpc_precheck,
+            ..self
+        }
+    }
+
+    /// Eject the endpoints that fail `checks` connection checks in a row, rather than keeping
+    /// them in the pool until the lookup service stops returning them.
+    ///
+    /// When enabled, every probe checks the new endpoints and the ones that failed their last
+    /// check with the request of the [`grpc_precheck`](Self::grpc_precheck), bounded by the
+    /// `connect_timeout`. This catches the endpoints that resolve to a valid uri but can never
+    /// serve a call, e.g. because they only speak TLS while TLS is disabled, which would
+    /// otherwise drag the balancing down. The ejection is logged with a diagnostic, and an
+    /// ejected endpoint is restored as soon as it passes a check again.
+    ///
+    /// Every check costs a new connection and a request, which the endpoints that keep failing
+    /// receive on every probe. The endpoints that passed their last check are not checked
+    /// again, so an endpoint failing after it has been committed is not ejected, and the new
+    /// endpoints that passed the `grpc_precheck` are not checked twice.
+    ///
+    /// Disabled by default, and `0` behaves as `1`.
+    pub fn eject_unconnectable_after(self, checks: u32) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            eject_unconnectable_after: Some(checks.max(1)),
+            ..self
+        }
+    }
+
+    /// Wait for a new set of endpoints to be ready before removing the current ones, when a
+    /// probe resolves an entirely different set, e.g. during a blue-green cutover.
+    ///
+    /// When enabled, a full replacement is applied in two steps: the new endpoints are
+    /// reported alongside the current ones, then the current ones are removed once at least
+    /// one of the new ones accepts a connection, bounded by the `connect_timeout`. If none
+    /// does, the new endpoints are withdrawn and the current ones keep serving until a later
+    /// probe finds the new set ready. Changes that keep some of the current endpoints are
+    /// applied as usual.
+    ///
+    /// Disabled by default.
+    pub fn zero_downtime_cutover(
+        self,
+        zero_downtime_cutover: bool,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            zero_downtime_cutover,
+            ..self
+        }
+    }
+
+    /// Log a `warn` event whenever a probe fails, e.g. because the name server is unreachable.
+    ///
+    /// Deployments where transient resolution failures are expected can disable the logs,
+    /// and still detect the failures through [`ProbeObserver::on_resolution`] and the
+    /// `last_error` of [`LoadBalancedChannel::snapshot`]. The probe keeps retrying either way.
+    ///
+    /// Enabled by default: failed probes are logged at the `warn` level, as the endpoints
+    /// are left unchanged until the next successful probe.
+    pub fn log_probe_errors(self, log_probe_errors: bool) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            log_probe_errors,
+            ..self
+        }
+    }
+
+    /// Set a [`ProbeObserver`] notified about the work of the probe, e.g. the duration of
+    /// every resolution.
+    ///
+    /// Keep a clone of `observer` to read what it has recorded.
+    pub fn probe_observer(
+        self,
+        observer: Arc<dyn ProbeObserver>,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            probe_observer: Some(observer),
+            ..self
+        }
+    }
+
+    /// Call `callback` whenever a probe finds endpoints after the channel had none,
+    /// including when the first endpoints are resolved.
+    ///
+    /// The callback runs on the probe task and should return quickly.
+    pub fn on_endpoints_available(
+        self,
+        callback: impl Fn() + Send + Sync + 'static,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            on_endpoints_available: Some(Box::new(callback)),
+            ..self
+        }
+    }
+
+    /// Call `callback` whenever a probe removes the last endpoints of the channel.
+    ///
+    /// The callback runs on the probe task and should return quickly.
+    pub fn on_endpoints_exhausted(
+        self,
+        callback: impl Fn() + Send + Sync + 'static,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            on_endpoints_exhausted: Some(Box::new(callback)),
+            ..self
+        }
+    }
+
+    /// Call `callback` every time the probe fails `failures` times in a row, e.g. to
+    /// recreate a resolver that got stuck or to page an operator.
+    ///
+    /// The count is reset by a successful probe, so that a probe failing forever fires the
+    /// callback after `failures` failures, then again after every `failures` more.
+    /// A `failures` of 0 is treated as 1. The probe itself keeps retrying as usual.
+    ///
+    /// The callback runs on the probe task and should return quickly.
+    pub fn on_consecutive_failures(
+        self,
+        failures: u32,
+        callback: impl Fn() + Send + Sync + 'static,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            on_consecutive_failures: Some((failures.max(1), Box::new(callback))),
+            ..self
+        }
+    }
+
+    /// Rewrite every resolved address with `rewrite` before an endpoint is built for it,
+    /// e.g. to map a service ip to a node ip or to remap ports behind a NAT.
+    ///
+    /// The channel only ever sees the rewritten addresses: they are the ones connected to,
+    /// reported by [`LoadBalancedChannel::snapshot`] and accepted by
+    /// [`LoadBalancedChannel::pin_endpoint`]. Addresses rewritten to the same value
+    /// are merged into a single endpoint.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use std::net::SocketAddr;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .address_rewrite(|address: SocketAddr| SocketAddr::new(address.ip(), 30000))
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn address_rewrite(
+        self,
+        rewrite: impl Fn(SocketAddr) -> SocketAddr + Send + Sync + 'static,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            address_rewrite: Some(Box::new(rewrite)),
+            ..self
+        }
+    }
+
+    /// Build the uri of the endpoint of every resolved address with `builder`, taking full
+    /// control of its scheme and authority, e.g. to speak HTTP/2 cleartext with prior
+    /// knowledge through a proxy addressed by a name and a port of its own.
+    ///
+    /// The uri replaces the `http` or `https` one derived from the TLS configuration, which
+    /// is still applied to the endpoint: tonic only negotiates TLS with `https` uris. The
+    /// other settings of the endpoints and the endpoint layers apply on top of it.
+    ///
+    /// The default uri of an IPv6 address with a scope id, e.g. a link-local one, keeps the
+    /// numeric scope id in its host, as in `[fe80::1%2]`. Such a host is resolved back into
+    /// the scoped address by the `getaddrinfo` of the system, on a blocking thread, every time
+    /// the endpoint connects.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use std::net::SocketAddr;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .endpoint_uri_builder(|address: &SocketAddr| {
+    ///             format!("http://{}:15001", address.ip())
+    ///                 .parse()
+    ///                 .expect("an ip and a port make a valid uri")
+    ///         })
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn endpoint_uri_builder(
+        self,
+        builder: impl Fn(&SocketAddr) -> http::Uri + Send + Sync + 'static,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            endpoint_uri_builder: Some(Box::new(builder)),
+            ..self
+        }
+    }
+
+    /// Reject every resolution that finds more than `limit` endpoints, e.g. because a buggy
+    /// lookup service returns an ever-growing set of addresses.
+    ///
+    /// Such a probe fails, as reported by the `last_error` of [`LoadBalancedChannel::snapshot`],
+    /// and the current endpoints are kept rather than opening connections until the client
+    /// runs out of resources. Unlike [`Self::max_open_connections`], nothing from such a
+    /// resolution is applied.
+    ///
+    /// Unlimited by default.
+    pub fn max_endpoints_hard_limit(self, limit: usize) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            max_endpoints_hard_limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Stop adding new endpoints once the channel balances over `max` of them, e.g. to bound
+    /// the file descriptors used by a client of a large pool.
+    ///
+    /// Every endpoint holds one connection, and the endpoints left out are added as soon
+    /// as others are removed. The endpoints already balanced over are never removed to
+    /// honour the cap, and the channels handed out by [`LoadBalancedChannel::per_endpoint_channels`]
+    /// are not capped, see [`LoadBalancedChannel::open_connection_count`].
+    pub fn max_open_connections(self, max: usize) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            max_open_connections: Some(max),
+            ..self
+        }
+    }
+
+    /// Open at most `concurrency` connections at a time while warming up the endpoints
+    /// with [`ResolutionStrategy::EagerConnect`], to avoid a connection storm when
+    /// starting against hundreds of endpoints. Unbounded by default.
+    ///
+    /// The endpoints that have not been connected to by the end of the resolution
+    /// timeout do not count as ready.
+    pub fn warmup_concurrency(self, concurrency: usize) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            warmup_concurrency: Some(concurrency),
+            ..self
+        }
+    }
+
+    /// Only remove an endpoint once it has been missing from `probes` consecutive
+    /// resolutions, while new endpoints are still added as soon as they are discovered.
+    ///
+    /// This trades a slower reaction to endpoints going away for stability against records
+    /// that flap, e.g. when DNS servers disagree. A failed probe does not count towards
+    /// the removal, and `0` behaves as the default of `1`, removing endpoints immediately.
+    pub fn removal_confirmations(self, probes: u32) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            removal_confirmations: probes,
+            ..self
+        }
+    }
+
+    /// Emit a `warn` event, and notify the [`ProbeObserver`], whenever a probe commits fewer
+    /// endpoints than `minimum`, e.g. to catch a partial outage before it becomes a total one.
+    ///
+    /// The warning fires once as the number of endpoints drops below `minimum`, including
+    /// when the first probe finds too few of them, and again only after it has recovered.
+    pub fn min_healthy_warn(self, minimum: usize) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            min_healthy_warn: Some(minimum),
+            ..self
+        }
+    }
+
+    /// Log the full set of endpoints at `info` level every `interval` probes, e.g. to keep
+    /// an audit trail of the topology the client believed in at any point in time.
+    ///
+    /// Disabled by default, or when `interval` is `0`.
+    pub fn snapshot_log_interval(self, interval: u32) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            snapshot_log_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Report a set of known endpoints, e.g. persisted by a previous run, as soon as
+    /// the channel is constructed.
+    ///
+    /// The channel is usable right away, without waiting for a blocking
+    /// [`ResolutionStrategy::Eager`] resolution, while the first probe runs in the background.
+    /// The probes reconcile the resolved endpoints against the seed, removing the seeded
+    /// endpoints that are not resolved anymore.
+    pub fn seed_endpoints(
+        self,
+        seed_endpoints: HashSet<SocketAddr>,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            seed_endpoints,
+            ..self
+        }
+    }
+
+    /// Configure the channel to use tls.
+    /// A `tls_config` MUST be specified to use the `HTTPS` scheme.
+    ///
+    /// The endpoints are connected to by ip, which is not the name their certificates
+    /// are issued for, so the domain name of `tls_config` is replaced with the hostname of
+    /// the [`ServiceDefinition`] unless [`Self::override_tls_domain_name`] is disabled.
+    pub fn with_tls(self, tls_config: ClientTlsConfig) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            tls_config: Some(tls_config),
+            ..self
+        }
+    }
+
+    /// Configure the channel to use tls, reading the certificate authority the endpoints are
+    /// verified with from the PEM file at `ca_path`, and the certificate and key of the client
+    /// from the PEM files of `identity` for mutual tls.
+    ///
+    /// Fails if a file can not be read or does not hold a PEM certificate or private key.
+    ///
+    /// ```rust,no_run
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .with_tls_from_files("/etc/tls/ca.pem", Some(("/etc/tls/client.pem", "/etc/tls/client.key")))
+    ///         .expect("invalid tls configuration")
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn with_tls_from_files<P: AsRef<Path>>(
+        self,
+        ca_path: P,
+        identity: Option<(P, P)>,
+    ) -> Result<LoadBalancedChannelBuilder<T, S>, anyhow::Error> {
+        let identity = identity
+            .as_ref()
+            .map(|(certificate, key)| (certificate.as_ref(), key.as_ref()));
+        let tls_config = crate::tls_files::client_tls_config(Some(ca_path.as_ref()), identity)?;
+
+        Ok(self.with_tls(tls_config))
+    }
+
+    /// Whether the domain name of the tls configurations is replaced with the hostname of
+    /// the [`ServiceDefinition`], `true` by default.
+    ///
+    /// Since the endpoints are connected to by ip, the certificates would otherwise be
+    /// verified against the ip of every endpoint and the handshake would fail.
+    /// Disable this if the tls configuration already sets the domain name the certificates
+    /// are issued for, e.g. when it differs from the hostname that is resolved, or
+    /// when a custom verifier relies on the domain name it has been given.
+    /// To verify the certificates against another fixed name, see [`Self::tls_domain_name`].
+    ///
+    /// This also applies to the configurations set with [`LoadBalancedChannel::set_tls_config`].
+    pub fn override_tls_domain_name(self, enabled: bool) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            override_tls_domain_name: enabled,
+            ..self
+        }
+    }
+
+    /// Verify the certificates of the endpoints against `domain_name` rather than the hostname
+    /// of the [`ServiceDefinition`], when [`Self::override_tls_domain_name`] is enabled.
+    ///
+    /// This is meant for backends that share the tls identity of a gateway while being
+    /// discovered, and connected to, by their own ip. The domain name also applies to the
+    /// configurations set with [`LoadBalancedChannel::set_tls_config`], so that rotating the
+    /// certificates does not require setting it again. If the backends route requests by
+    /// their authority as well, set the origin of every endpoint to the gateway with
+    /// [`Self::try_with_endpoint_layer`]:
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::LoadBalancedChannel;
+    ///     use tonic::transport::ClientTlsConfig;
+    ///
+    ///     let origin: http::Uri = "https://gateway.example.com".parse().unwrap();
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("backends.internal", 5000))
+    ///         .with_tls(ClientTlsConfig::new())
+    ///         .tls_domain_name("gateway.example.com")
+    ///         .try_with_endpoint_layer(move |endpoint| {
+    ///             Ok::<_, std::convert::Infallible>(endpoint.origin(origin.clone()))
+    ///         })
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn tls_domain_name(
+        self,
+        domain_name: impl Into<String>,
+    ) -> LoadBalancedChannelBuilder<T, S> {
+        Self {
+            tls_domain_name: Some(domain_name.into()),
+            ..self
+        }
+    }
+
+    /// Construct a [`LoadBalancedChannel`] from the [`LoadBalancedChannelBuilder`] instance.
+    pub async fn channel(mut self) -> Result<LoadBalancedChannel, anyhow::Error> {
+        if self.mesh_passthrough {
+            return self.passthrough_channel();
+        }
+
+        match self.lookup_service.take() {
+            Some(lookup_service) => self.channel_inner(lookup_service).await,
+            None => {
+                let resolver = self.system_dns_resolver().await?;
+                self.channel_inner(resolver).await
+            }
+        }
+    }
+
+    /// Construct an [`EndpointDiscover`] from the [`LoadBalancedChannelBuilder`] instance,
+    /// to feed the discovered endpoints to a balancer of your choice.
+    ///
+    /// The endpoints are configured with the timeouts and TLS settings of the builder,
+    /// while the settings of the channel itself (e.g. the channel-level layers) are ignored.
+    pub async fn discover(mut self) -> Result<EndpointDiscover, anyhow::Error> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(GRPC_REPORT_ENDPOINTS_CHANNEL_SIZE);
+
+        match self.lookup_service.take() {
+            Some(lookup_service) => self.start_probe(lookup_service, sender).await?,
+            None => {
+                let resolver = self.system_dns_resolver().await?;
+                self.start_probe(resolver, sender).await?
+            }
+        };
+
+        Ok(EndpointDiscover::new(receiver))
+    }
+
+    /// Read the [`DnsResolver`] used when no [`LookupService`] has been set.
+    async fn system_dns_resolver(&self) -> Result<DnsResolver, anyhow::Error> {
+        Ok(DnsResolver::from_system_config()
+            .await?
+            .with_record_kind(self.dns_record_kind))
+    }
+
+    async fn channel_inner<U>(
+        mut self,
+        lookup_service: U,
+    ) -> Result<LoadBalancedChannel, anyhow::Error>
     where
         U: LookupService + Send + Sync + 'static + Sized,
     {
         let (channel, sender) = Channel::balance_channel(GRPC_REPORT_ENDPOINTS_CHANNEL_SIZE);
+        let (layers, settings) = self.channel_layers_and_settings();
+
+        let state = self.start_probe(lookup_service, sender).await?;
+
+        Ok(LoadBalancedChannel {
+            balanced: layers.route(channel),
+            layers,
+            pinned: Default::default(),
+            ready: ReadyRoute::Balanced,
+            settings,
+            state,
+        })
+    }
+
+    /// Construct a channel connecting to the hostname of the service definition itself,
+    /// for [`mesh_passthrough`](Self::mesh_passthrough).
+    fn passthrough_channel(mut self) -> Result<LoadBalancedChannel, anyhow::Error> {
+        if self.lookup_service.is_some() {
+            tracing::warn!("the lookup service is not used in mesh passthrough mode");
+        }
+        let (layers, settings) = self.channel_layers_and_settings();
+        let endpoint_timeout = self.endpoint_timeout();
+        let endpoint_connect_timeout = self.endpoint_connect_timeout();
+        let service_definition: ServiceDefinition = self
+            .service_definition
+            .try_into()
+            .map_err(Into::into)
+            .map_err(|err| anyhow::anyhow!(err))?;
+        let state = Arc::new(probe_state(
+            service_definition,
+            self.tls_domain_name,
+            self.override_tls_domain_name,
+        ));
+        if let Some(tls_config) = self.tls_config {
+            state.set_tls_config(tls_config);
+        }
+        // Nothing is ever resolved, the channel is as ready as it gets.
+        state.mark_resolved();
+
+        let service_definition = state.service_definition();
+        let scheme = if state.is_tls_enabled() {
+            http::uri::Scheme::HTTPS
+        } else {
+            http::uri::Scheme::HTTP
+        };
+        let mut endpoint = Endpoint::from_shared(format!(
+            "{}://{}:{}",
+            scheme,
+            service_definition.hostname(),
+            service_definition.port()
+        ))
+        .context("endpoint creation error")?;
+        if let Some(tls_config) = state.tls_config() {
+            endpoint = endpoint.tls_config(tls_config).context("tls error")?;
+        }
+        if let Some(timeout) = endpoint_timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some(connect_timeout) = endpoint_connect_timeout {
+            endpoint = endpoint.connect_timeout(connect_timeout);
+        }
+        if let Some(enabled) = self.http2_adaptive_window {
+            endpoint = endpoint.http2_adaptive_window(enabled);
+        }
+
+        Ok(LoadBalancedChannel {
+            balanced: layers.route(endpoint.connect_lazy()),
+            layers,
+            pinned: Default::default(),
+            ready: ReadyRoute::Balanced,
+            settings,
+            state,
+        })
+    }
+
+    /// Take the channel-level layers and the settings of the channel out of the builder.
+    fn channel_layers_and_settings(&mut self) -> (ChannelLayers, ChannelSettings) {
+        // The request timeout only needs to be applied to the channel as a whole
+        // when the endpoints enforce a separate per-try timeout.
+        let overall_timeout = self
+            .overall_timeout
+            .or(self.timeout.filter(|_| self.timeout_per_try.is_some()));
+        let layers = ChannelLayers {
+            channel_layer: self.channel_layer.take(),
+            overall_timeout,
+            global_concurrency_limit: self.global_concurrency_limit,
+            default_headers: self.default_headers.take(),
+        };
+        let settings = ChannelSettings {
+            probe_interval: self.effective_probe_interval(),
+            timeout: overall_timeout.or(self.endpoint_timeout()),
+            connect_timeout: self.endpoint_connect_timeout(),
+            reprobe_on_unavailable: self.reprobe_on_unavailable,
+        };
+
+        (layers, settings)
+    }
+
+    /// The interval between two probes, unless a lookup service hints otherwise.
+    fn effective_probe_interval(&self) -> Duration {
+        self.probe_interval.unwrap_or(DEFAULT_PROBE_INTERVAL)
+    }
+
+    /// The timeout applied by every endpoint: the timeout of a single try, if any.
+    fn endpoint_timeout(&self) -> Option<Duration> {
+        self.timeout_per_try.or(self.timeout)
+    }
+
+    /// The timeout applied to the connection to every endpoint, which defaults to the
+    /// timeout of the requests.
+    fn endpoint_connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout.or(self.endpoint_timeout())
+    }
+
+    /// Spawn a probe reporting the endpoint changes to `sender`, after resolving
+    /// the endpoints once if the [`ResolutionStrategy`] is eager.
+    async fn start_probe<U>(
+        self,
+        lookup_service: U,
+        sender: Sender<Change<SocketAddr, Endpoint>>,
+    ) -> Result<Arc<ProbeState>, anyhow::Error>
+    where
+        U: LookupService + Send + Sync + 'static + Sized,
+    {
+        let endpoint_timeout = self.endpoint_timeout();
+        let endpoint_connect_timeout = self.endpoint_connect_timeout();
+        let probe_interval = self.effective_probe_interval();
+        let service_definition: ServiceDefinition = self
+            .service_definition
+            .try_into()
+            .map_err(Into::into)
+            .map_err(|err| anyhow::anyhow!(err))?;
+        let tls_domain_name = service_definition.hostname().to_string();
+        let span = tracing::info_span!(
+            "ginepro_probe",
+            channel_name = self.name.as_deref(),
+            hostname = %tls_domain_name
+        );
+        let state = Arc::new(probe_state(
+            service_definition,
+            self.tls_domain_name,
+            self.override_tls_domain_name,
+        ));
 
         let config = GrpcServiceProbeConfig {
-            service_definition: self
-                .service_definition
-                .try_into()
-                .map_err(Into::into)
-                .map_err(|err| anyhow::anyhow!(err))?,
+            state: Arc::clone(&state),
             dns_lookup: lookup_service,
-            endpoint_timeout: self.timeout,
-            endpoint_connect_timeout: self.connect_timeout.or(self.timeout),
-            probe_interval: self
-                .probe_interval
-                .unwrap_or_else(|| Duration::from_secs(10)),
+            endpoint_timeout,
+            endpoint_connect_timeout,
+            http2_adaptive_window: self.http2_adaptive_window,
+            endpoint_layer: self.endpoint_layer,
+            probe_interval,
+            change_ordering: self.change_ordering,
+            partial_result_policy: self.partial_result_policy,
+            full_buffer_policy: self.full_buffer_policy,
+            tcp_precheck: self.tcp_precheck,
+            grpc_precheck: self.grpc_precheck,
+            observer: self.probe_observer,
+            snapshot_log_interval: self.snapshot_log_interval,
+            on_endpoints_available: self.on_endpoints_available,
+            on_endpoints_exhausted: self.on_endpoints_exhausted,
+            address_rewrite: self.address_rewrite,
+            min_healthy_warn: self.min_healthy_warn,
+            removal_confirmations: self.removal_confirmations,
+            max_open_connections: self.max_open_connections,
+            zero_downtime_cutover: self.zero_downtime_cutover,
+            on_consecutive_failures: self.on_consecutive_failures,
+            log_probe_errors: self.log_probe_errors,
+            max_endpoints_hard_limit: self.max_endpoints_hard_limit,
+            initial_probe_jitter: self.initial_probe_jitter,
+            endpoint_uri_builder: self.endpoint_uri_builder,
+            adaptive_interval: self.adaptive_interval,
+            eject_unconnectable_after: self.eject_unconnectable_after,
+            change_report_threshold: self.change_report_threshold,
+            balancing_seed: self.balancing_seed,
         };
 
-        let tls_config = self.tls_config.map(|mut tls_config| {
-            // Since we resolve the hostname to an IP, which is not a valid DNS name,
-            // we have to set the hostname explicitly on the tls config,
-            // otherwise the IP will be set as the domain name and tls handshake will fail.
-            tls_config = tls_config.domain_name(config.service_definition.hostname());
-
-            tls_config
-        });
+        if let Some(tls_config) = self.tls_config {
+            state.set_tls_config(tls_config);
+        }
 
         let mut service_probe = GrpcServiceProbe::new_with_reporter(config, sender);
 
-        if let Some(tls_config) = tls_config {
-            service_probe = service_probe.with_tls(tls_config);
+        if !self.seed_endpoints.is_empty() {
+            service_probe
+                .seed(self.seed_endpoints)
+                .instrument(span.clone())
+                .await
+                .context("failed to report the seed endpoints")?;
         }
 
-        if let ResolutionStrategy::Eager { timeout } = self.resolution_strategy {
-            // Make sure we resolve the hostname once before we create the channel.
-            tokio::time::timeout(timeout, service_probe.probe_once())
-                .await
-                .context("timeout out while attempting to resolve IPs")?
-                .context("failed to resolve IPs")?;
+        let eager = match self.resolution_strategy {
+            ResolutionStrategy::Lazy => None,
+            ResolutionStrategy::Eager { timeout } | ResolutionStrategy::Once { timeout } => {
+                Some((timeout, None))
+            }
+            ResolutionStrategy::EagerConnect { timeout, min_ready } => {
+                Some((timeout, Some(min_ready)))
+            }
+        };
+        if let Some((timeout, min_ready)) = eager {
+            let deadline = tokio::time::Instant::now() + timeout;
+            let mut retry_delay = self.eager_retry_delay;
+
+            // Make sure we resolve the hostname once before we create the channel,
+            // retrying until we find endpoints if asked to.
+            loop {
+                let resolution = tokio::time::timeout_at(
+                    deadline,
+                    service_probe.probe_once().instrument(span.clone()),
+                )
+                .await;
+                let resolved =
+                    matches!(resolution, Ok(Ok(()))) && !service_probe.endpoints().is_empty();
+
+                match retry_delay {
+                    Some(delay) if !resolved && tokio::time::Instant::now() + delay < deadline => {
+                        tracing::debug!("no endpoints resolved yet, retrying in {:?}", delay);
+                        DefaultRuntime::sleep(delay).await;
+                        retry_delay = Some(delay * 2);
+                    }
+                    _ => {
+                        resolution
+                            .context("timeout out while attempting to resolve IPs")?
+                            .context("failed to resolve IPs")?;
+                        break;
+                    }
+                }
+            }
+
+            if self.fail_on_empty_resolution && service_probe.endpoints().is_empty() {
+                return Err(ResolutionError::NoEndpoints.into());
+            }
+
+            if let Some(min_ready) = min_ready {
+                let ready = connect_endpoints(&state, deadline, self.warmup_concurrency)
+                    .instrument(span.clone())
+                    .await;
+                state.set_ready_at_startup(ready);
+                if ready < min_ready {
+                    return Err(ResolutionError::NotEnoughReadyEndpoints {
+                        ready,
+                        required: min_ready,
+                    }
+                    .into());
+                }
+            }
         }
 
-        tokio::spawn(service_probe.probe());
+        if matches!(self.resolution_strategy, ResolutionStrategy::Once { .. }) {
+            // Dropping the probe closes the discovery stream, and tonic keeps
+            // balancing over the endpoints it has been reported.
+            tracing::debug!(parent: &span, "resolved once, not probing any further");
+        } else {
+            DefaultRuntime::spawn(service_probe.probe().instrument(span));
+        }
+
+        Ok(state)
+    }
+}
 
-        Ok(LoadBalancedChannel(channel))
+/// Create the state shared by a channel and its probe, verifying the endpoints against
+/// `tls_domain_name` if set, or else against the hostname of `service_definition`
+/// unless `override_tls_domain_name` is disabled.
+fn probe_state(
+    service_definition: ServiceDefinition,
+    tls_domain_name: Option<String>,
+    override_tls_domain_name: bool,
+) -> ProbeState {
+    let mut state = ProbeState::new(service_definition);
+    if let Some(tls_domain_name) = tls_domain_name {
+        state = state.with_tls_domain_name(tls_domain_name);
+    }
+    if !override_tls_domain_name {
+        state = state.without_tls_domain_name_override();
     }
+    state
+}
+
+/// Connect to every endpoint reported to tonic until `deadline`, at most `concurrency`
+/// at a time, returning how many of them accepted a connection.
+async fn connect_endpoints(
+    state: &ProbeState,
+    deadline: tokio::time::Instant,
+    concurrency: Option<usize>,
+) -> usize {
+    let endpoints = state.endpoints();
+    let concurrency = concurrency.unwrap_or(endpoints.len()).max(1);
+
+    futures::stream::iter(endpoints)
+        .map(|(address, endpoint)| async move {
+            match tokio::time::timeout_at(deadline, endpoint.connect()).await {
+                Ok(Ok(_)) => true,
+                Ok(Err(err)) => {
+                    let hint = tls_mismatch_hint(state.is_tls_enabled(), &err);
+                    tracing::warn!("failed to connect to {}{}: {:?}", address, hint, err);
+                    false
+                }
+                Err(_) => {
+                    tracing::warn!("timed out connecting to {}", address);
+                    false
+                }
+            }
+        })
+        .buffer_unordered(concurrency)
+        .filter(|connected| futures::future::ready(*connected))
+        .count()
+        .await
 }
 
 const _: () = {
     const fn assert_is_send<T: Send>() {}
+    const fn assert_is_sync<T: Sync>() {}
     assert_is_send::<LoadBalancedChannelBuilder<DnsResolver, ServiceDefinition>>();
     assert_is_send::<LoadBalancedChannel>();
+    assert_is_sync::<LoadBalancedChannel>();
 };
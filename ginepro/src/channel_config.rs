@@ -0,0 +1,187 @@
+//! A [`LoadBalancedChannel`](crate::LoadBalancedChannel) configuration read with serde.
+
+use crate::{DnsResolver, LoadBalancedChannelBuilder, ResolutionStrategy, ServiceDefinition};
+use std::path::PathBuf;
+use std::time::Duration;
+use tonic::transport::ClientTlsConfig;
+
+/// The settings of a [`LoadBalancedChannel`](crate::LoadBalancedChannel) as they appear
+/// in a configuration file, available with the `serde` feature.
+///
+/// Durations are expressed in milliseconds, and every optional setting left out
+/// keeps the default of [`LoadBalancedChannelBuilder`].
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::LoadBalancedChannelConfig;
+///
+///     let config: LoadBalancedChannelConfig = serde_json::from_str(
+///         r#"{ "hostname": "my.hostname", "port": 5000, "timeout_ms": 500 }"#,
+///     )
+///     .expect("invalid configuration");
+///
+///     let load_balanced_channel = config
+///         .builder()
+///         .expect("invalid configuration")
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoadBalancedChannelConfig {
+    /// The hostname of the service, see [`ServiceDefinition::hostname`].
+    pub hostname: String,
+    /// The port of the service, see [`ServiceDefinition::port`].
+    pub port: u16,
+    /// See [`LoadBalancedChannelBuilder::dns_probe_interval`].
+    #[serde(default)]
+    pub probe_interval_ms: Option<u64>,
+    /// See [`LoadBalancedChannelBuilder::timeout`].
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// See [`LoadBalancedChannelBuilder::timeout_per_try`].
+    #[serde(default)]
+    pub timeout_per_try_ms: Option<u64>,
+    /// See [`LoadBalancedChannelBuilder::overall_timeout`].
+    #[serde(default)]
+    pub overall_timeout_ms: Option<u64>,
+    /// See [`LoadBalancedChannelBuilder::connect_timeout`].
+    #[serde(default)]
+    pub connect_timeout_ms: Option<u64>,
+    /// Resolve the hostname before the channel is constructed, giving up after this
+    /// timeout, i.e. [`ResolutionStrategy::Eager`]. The resolution is lazy if unset.
+    #[serde(default)]
+    pub eager_resolution_timeout_ms: Option<u64>,
+    /// Connect to the endpoints over TLS.
+    #[serde(default)]
+    pub tls: Option<TlsFilesConfig>,
+}
+
+/// The PEM files a [`LoadBalancedChannelConfig`] reads its [`ClientTlsConfig`] from.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsFilesConfig {
+    /// The certificate of the authority the server certificates are verified with.
+    #[serde(default)]
+    pub ca_certificate: Option<PathBuf>,
+    /// The client certificate, for mutual TLS. Requires `client_key`.
+    #[serde(default)]
+    pub client_certificate: Option<PathBuf>,
+    /// The key of the client certificate, for mutual TLS. Requires `client_certificate`.
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+    /// The name the server certificates are verified against, instead of the hostname.
+    #[serde(default)]
+    pub domain_name: Option<String>,
+}
+
+impl LoadBalancedChannelConfig {
+    /// Validate the service definition, read the TLS files and configure a
+    /// [`LoadBalancedChannelBuilder`] with the settings.
+    ///
+    /// The returned builder can be customised further before constructing the channel.
+    pub fn builder(
+        &self,
+    ) -> Result<LoadBalancedChannelBuilder<DnsResolver, ServiceDefinition>, anyhow::Error> {
+        let service_definition = ServiceDefinition::from_parts(&self.hostname, self.port)?;
+        let mut builder = LoadBalancedChannelBuilder::new_with_service(service_definition);
+
+        if let Some(interval) = self.probe_interval_ms {
+            builder = builder.dns_probe_interval(Duration::from_millis(interval));
+        }
+        if let Some(timeout) = self.timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.timeout_per_try_ms {
+            builder = builder.timeout_per_try(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.overall_timeout_ms {
+            builder = builder.overall_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.eager_resolution_timeout_ms {
+            builder = builder.resolution_strategy(ResolutionStrategy::Eager {
+                timeout: Duration::from_millis(timeout),
+            });
+        }
+        if let Some(tls) = &self.tls {
+            builder = builder.with_tls(tls.client_tls_config()?);
+        }
+
+        Ok(builder)
+    }
+}
+
+impl TlsFilesConfig {
+    /// Read the PEM files into a [`ClientTlsConfig`].
+    pub fn client_tls_config(&self) -> Result<ClientTlsConfig, anyhow::Error> {
+        let identity = match (&self.client_certificate, &self.client_key) {
+            (Some(certificate), Some(key)) => Some((certificate.as_path(), key.as_path())),
+            (None, None) => None,
+            _ => anyhow::bail!("a client certificate and its key must be configured together"),
+        };
+        let mut tls_config =
+            crate::tls_files::client_tls_config(self.ca_certificate.as_deref(), identity)?;
+        if let Some(domain_name) = &self.domain_name {
+            tls_config = tls_config.domain_name(domain_name);
+        }
+
+        Ok(tls_config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn settings_left_out_keep_their_default() {
+        let config: LoadBalancedChannelConfig =
+            serde_json::from_str(r#"{ "hostname": "my.hostname", "port": 5000 }"#).unwrap();
+
+        assert_eq!(config.probe_interval_ms, None);
+        assert_eq!(config.eager_resolution_timeout_ms, None);
+        assert_eq!(config.tls, None);
+        assert!(config.builder().is_ok());
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let config = serde_json::from_str::<LoadBalancedChannelConfig>(
+            r#"{ "hostname": "my.hostname", "port": 5000, "timeout": 500 }"#,
+        );
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn invalid_hostnames_are_rejected_by_the_builder() {
+        let config: LoadBalancedChannelConfig =
+            serde_json::from_str(r#"{ "hostname": "", "port": 5000 }"#).unwrap();
+
+        assert!(config.builder().is_err());
+    }
+
+    #[test]
+    fn missing_tls_files_are_reported() {
+        let config: LoadBalancedChannelConfig = serde_json::from_str(
+            r#"{
+                "hostname": "my.hostname",
+                "port": 5000,
+                "tls": { "ca_certificate": "/does/not/exist.pem" }
+            }"#,
+        )
+        .unwrap();
+
+        let error = config
+            .builder()
+            .err()
+            .expect("the ca certificate is missing");
+        assert!(format!("{:#}", error).contains("/does/not/exist.pem"));
+    }
+}
@@ -0,0 +1,45 @@
+//! A point-in-time view of the endpoints discovered by the probe.
+
+use crate::ServiceDefinition;
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+/// A snapshot of the state of the probe of a [`LoadBalancedChannel`](crate::LoadBalancedChannel),
+/// taken with [`LoadBalancedChannel::snapshot`](crate::LoadBalancedChannel::snapshot).
+///
+/// It is meant to be exposed by the admin or debug endpoints of an application,
+/// and implements `serde::Serialize` when the `serde` feature is enabled.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::LoadBalancedChannel;
+///
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+///
+///     let snapshot = load_balanced_channel.snapshot();
+///     println!(
+///         "{} endpoints after {} changes, last error: {:?}",
+///         snapshot.endpoints.len(),
+///         snapshot.generation,
+///         snapshot.last_error
+///     );
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DiscoverySnapshot {
+    /// The service definitions whose endpoints are merged into a single set.
+    pub service_definitions: Vec<ServiceDefinition>,
+    /// The endpoints reported to tonic, in ascending order.
+    pub endpoints: Vec<SocketAddr>,
+    /// When the last probe completed, successfully or not.
+    pub last_probe: Option<SystemTime>,
+    /// Why the last probe failed, if it did.
+    pub last_error: Option<String>,
+    /// The number of times the set of endpoints reported to tonic has changed.
+    pub generation: u64,
+}
@@ -1,16 +1,84 @@
 //! Implements [`LookupService`] for dns.
 
-use crate::{LookupService, ServiceDefinition};
+use crate::{LookupService, Resolution, ServiceDefinition};
 use anyhow::Context;
-use hickory_resolver::{system_conf, AsyncResolver, TokioAsyncResolver};
+use hickory_resolver::{
+    config::{LookupIpStrategy, ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    lookup::Lookup,
+    proto::rr::{Name, RecordType},
+    system_conf, AsyncResolver, TokioAsyncResolver,
+};
 use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::time::Instant;
 
 /// Implements [`LookupService`] by using DNS queries to lookup [`ServiceDefinition::hostname`].
+///
+/// Cloning a [`DnsResolver`] is cheap and the clones share the same underlying resolver,
+/// which allows one instance to be reused across many channels instead of reading the
+/// system configuration for each of them.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::{DnsResolver, LoadBalancedChannel};
+///
+///     let resolver = DnsResolver::from_system_config()
+///         .await
+///         .expect("failed to read the system dns configuration");
+///
+///     for hostname in ["my.hostname", "my.other.hostname"] {
+///         let load_balanced_channel = LoadBalancedChannel::builder((hostname, 5000))
+///             .lookup_service(resolver.clone())
+///             .channel()
+///             .await
+///             .expect("failed to construct LoadBalancedChannel");
+///     }
+/// }
+/// ```
+#[derive(Clone)]
 pub struct DnsResolver {
     /// The trust-dns resolver which contacts the dns service directly such
     /// that we bypass os-specific dns caching.
     dns: TokioAsyncResolver,
+    /// The configuration `dns` has been built from, kept to rebuild it with other options.
+    config: ResolverConfig,
+    opts: ResolverOpts,
+    /// The records queried to resolve a hostname.
+    record_kind: DnsRecordKind,
+}
+
+/// Enumerates the DNS records a [`DnsResolver`] queries to resolve a [`ServiceDefinition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DnsRecordKind {
+    /// Query the `A`/`AAAA` records of the hostname, and use the port of the
+    /// [`ServiceDefinition`].
+    #[default]
+    Ip,
+    /// Query the `SRV` records of the hostname and resolve the ips of their targets,
+    /// using the port of every record. If the hostname has no `SRV` records, e.g. because
+    /// it does not exist, fall back to [`DnsRecordKind::Ip`].
+    ///
+    /// The priority and weight of the records are ignored: all the targets are balanced over.
+    /// A target that fails to resolve is logged and skipped, and so is the target `.`,
+    /// by which a record states that the service is not available.
+    ///
+    /// With TLS, the certificates of the endpoints are verified against the hostname by
+    /// default, i.e. the name of the `SRV` records: set
+    /// [`LoadBalancedChannelBuilder::tls_domain_name`](crate::LoadBalancedChannelBuilder::tls_domain_name)
+    /// to the name the certificates are issued for.
+    SrvWithIpFallback,
+    /// Query the `SRV` records of the hostname and resolve the ips of their targets,
+    /// using the port of every record: the port of the [`ServiceDefinition`] is ignored.
+    /// The resolution fails if the hostname has no `SRV` records.
+    ///
+    /// The targets are handled as with [`DnsRecordKind::SrvWithIpFallback`]: the priority
+    /// and weight of the records are ignored, and the targets that fail to resolve or state
+    /// that the service is not available are skipped. With TLS,
+    /// [`LoadBalancedChannelBuilder::tls_domain_name`](crate::LoadBalancedChannelBuilder::tls_domain_name)
+    /// must be set, as no certificate is issued for the name of the `SRV` records.
+    Srv,
 }
 
 impl DnsResolver {
@@ -22,31 +90,343 @@ impl DnsResolver {
         // We do not want any caching on our side.
         opts.cache_size = 0;
 
-        let dns = AsyncResolver::tokio(config, opts);
+        let dns = AsyncResolver::tokio(config.clone(), opts.clone());
+
+        Ok(Self {
+            dns,
+            config,
+            opts,
+            record_kind: DnsRecordKind::default(),
+        })
+    }
+
+    /// Set the address families queried when resolving a hostname, the system
+    /// default being [`LookupIpStrategy::Ipv4thenIpv6`].
+    ///
+    /// This decides which records are queried rather than filtering the resolved ips:
+    /// * [`LookupIpStrategy::Ipv4Only`] and [`LookupIpStrategy::Ipv6Only`] only query
+    ///   `A` or `AAAA` records;
+    /// * [`LookupIpStrategy::Ipv4AndIpv6`] queries both and balances over all the ips,
+    ///   which on a dual-stack network means reaching every server over both families;
+    /// * [`LookupIpStrategy::Ipv4thenIpv6`] and [`LookupIpStrategy::Ipv6thenIpv4`] only query
+    ///   the second family if the first one has no records, so a dual-stack service is only
+    ///   reached over the preferred family.
+    ///
+    /// The returned [`DnsResolver`] does not share its underlying resolver with
+    /// the clones of `self`.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::{hickory_resolver::config::LookupIpStrategy, DnsResolver};
+    ///
+    ///     let resolver = DnsResolver::from_system_config()
+    ///         .await
+    ///         .expect("failed to read the system dns configuration")
+    ///         .with_ip_strategy(LookupIpStrategy::Ipv6Only);
+    /// }
+    /// ```
+    pub fn with_ip_strategy(self, ip_strategy: LookupIpStrategy) -> Self {
+        let mut opts = self.opts;
+        opts.ip_strategy = ip_strategy;
+
+        let dns = AsyncResolver::tokio(self.config.clone(), opts.clone());
+
+        Self {
+            dns,
+            config: self.config,
+            opts,
+            record_kind: self.record_kind,
+        }
+    }
+
+    /// Set the kind of records queried to resolve a hostname, [`DnsRecordKind::Ip`] by default.
+    ///
+    /// ```rust
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     use ginepro::{DnsRecordKind, DnsResolver, LoadBalancedChannel};
+    ///
+    ///     let resolver = DnsResolver::from_system_config()
+    ///         .await
+    ///         .expect("failed to read the system dns configuration")
+    ///         .with_record_kind(DnsRecordKind::SrvWithIpFallback);
+    ///
+    ///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+    ///         .lookup_service(resolver)
+    ///         .channel()
+    ///         .await
+    ///         .expect("failed to construct LoadBalancedChannel");
+    /// }
+    /// ```
+    pub fn with_record_kind(self, record_kind: DnsRecordKind) -> Self {
+        Self {
+            record_kind,
+            ..self
+        }
+    }
+
+    /// Get the kind of records queried to resolve a hostname.
+    pub fn record_kind(&self) -> DnsRecordKind {
+        self.record_kind
+    }
+
+    /// Get the underlying [`hickory_resolver`] resolver, configured without caching.
+    ///
+    /// This allows a custom [`LookupService`] built on top of [`DnsResolver`] to issue
+    /// its own queries.
+    pub fn resolver(&self) -> &TokioAsyncResolver {
+        &self.dns
+    }
+
+    /// Query the records of type `record_type` associated with `name`,
+    /// e.g. the `TXT` records carrying metadata about the instances of a service.
+    ///
+    /// ```rust,no_run
+    /// # async fn run() -> Result<(), anyhow::Error> {
+    /// use ginepro::{hickory_resolver::proto::rr::RecordType, DnsResolver};
+    ///
+    /// let resolver = DnsResolver::from_system_config().await?;
+    /// for record in resolver.lookup("my.hostname", RecordType::TXT).await?.iter() {
+    ///     println!("{}", record);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub async fn lookup(
+        &self,
+        name: &str,
+        record_type: RecordType,
+    ) -> Result<Lookup, anyhow::Error> {
+        self.dns
+            .lookup(name, record_type)
+            .await
+            .with_context(|| format!("failed to lookup {} records for '{}'", record_type, name))
+    }
+
+    /// Resolve the ips of `hostname`, using `port` for every one of them,
+    /// along with the instant the records expire.
+    async fn resolve_ips(
+        &self,
+        hostname: &str,
+        port: u16,
+    ) -> Result<(Vec<SocketAddr>, Instant), anyhow::Error> {
+        let lookup = self.dns.lookup_ip(hostname).await?;
+
+        tracing::debug!("dns query expires in: {:?}", lookup.valid_until());
+        let endpoints = lookup
+            .iter()
+            .map(|ip_addr| {
+                tracing::debug!("result: ip {}", ip_addr);
+                SocketAddr::from((ip_addr, port))
+            })
+            .collect();
+
+        Ok((endpoints, lookup.valid_until()))
+    }
+
+    /// Resolve the `SRV` records of `hostname`, then the ips of their targets using
+    /// the port of every record, along with the instant the first of those records expires.
+    /// Returns `None` if `hostname` has no `SRV` records.
+    ///
+    /// See [`DnsResolver::resolve_srv_targets`] for the targets that fail to resolve.
+    async fn resolve_srv(
+        &self,
+        hostname: &str,
+    ) -> Result<Option<(Vec<SocketAddr>, Instant)>, anyhow::Error> {
+        let lookup = match self.dns.srv_lookup(hostname).await {
+            Ok(lookup) => lookup,
+            Err(err) if matches!(err.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let targets: Vec<_> = lookup
+            .iter()
+            .map(|record| {
+                tracing::debug!("result: srv {}:{}", record.target(), record.port());
+                (record.target().clone(), record.port())
+            })
+            .collect();
+        if targets.is_empty() {
+            return Ok(None);
+        }
+
+        let (endpoints, valid_until) = self.resolve_srv_targets(hostname, &targets).await?;
+        let valid_until = valid_until
+            .into_iter()
+            .fold(lookup.as_lookup().valid_until(), Instant::min);
+
+        Ok(Some((endpoints, valid_until)))
+    }
+
+    /// Resolve the ips of the `targets` of the `SRV` records of `hostname`, using the port
+    /// of every record, along with the instant the first of their records expires.
+    ///
+    /// The target `.` states that the service is not available (RFC 2782) and is skipped.
+    /// A target that fails to resolve is logged and skipped, so that a single missing
+    /// instance does not fail the whole resolution: it only fails if every target does.
+    async fn resolve_srv_targets(
+        &self,
+        hostname: &str,
+        targets: &[(Name, u16)],
+    ) -> Result<(Vec<SocketAddr>, Option<Instant>), anyhow::Error> {
+        let resolved =
+            futures::future::join_all(targets.iter().filter(|(target, _)| !target.is_root()).map(
+                |(target, port)| async move {
+                    (target, self.resolve_ips(&target.to_utf8(), *port).await)
+                },
+            ))
+            .await;
 
-        Ok(Self { dns })
+        let mut endpoints = Vec::new();
+        let mut valid_until: Option<Instant> = None;
+        let mut last_error = None;
+        for (target, result) in resolved {
+            match result {
+                Ok((resolved, expires)) => {
+                    endpoints.extend(resolved);
+                    valid_until = Some(valid_until.map_or(expires, |until| until.min(expires)));
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "skipping srv target '{}' of '{}': {:?}",
+                        target,
+                        hostname,
+                        err
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        match last_error {
+            Some(err) if valid_until.is_none() => Err(err.context(format!(
+                "failed to resolve any srv target of '{}'",
+                hostname
+            ))),
+            _ => Ok((endpoints, valid_until)),
+        }
+    }
+
+    /// Resolve `hostname` according to the [`DnsRecordKind`].
+    async fn resolve_hostname(
+        &self,
+        hostname: &str,
+        port: u16,
+    ) -> Result<(Vec<SocketAddr>, Instant), anyhow::Error> {
+        match self.record_kind {
+            DnsRecordKind::Ip => self.resolve_ips(hostname, port).await,
+            DnsRecordKind::Srv => self
+                .resolve_srv(hostname)
+                .await?
+                .with_context(|| format!("no srv records found for '{}'", hostname)),
+            DnsRecordKind::SrvWithIpFallback => match self.resolve_srv(hostname).await? {
+                Some(resolved) => Ok(resolved),
+                None => {
+                    tracing::debug!("no srv records for '{}', resolving its ips", hostname);
+                    self.resolve_ips(hostname, port).await
+                }
+            },
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl LookupService for DnsResolver {
-    #[tracing::instrument(level = "debug", skip(self))]
     async fn resolve_service_endpoints(
         &self,
         definition: &ServiceDefinition,
     ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
-        match self.dns.lookup_ip(definition.hostname()).await {
-            Ok(lookup) => {
-                tracing::debug!("dns query expires in: {:?}", lookup.valid_until());
-                Ok(lookup
-                    .iter()
-                    .map(|ip_addr| {
-                        tracing::debug!("result: ip {}", ip_addr);
-                        (ip_addr, definition.port()).into()
-                    })
-                    .collect())
-            }
-            Err(err) => Err(err.into()),
+        self.resolve_service_endpoints_with_metadata(definition)
+            .await
+            .map(|resolution| resolution.endpoints)
+    }
+
+    /// Resolve every hostname of the [`ServiceDefinition`] concurrently, according to
+    /// the [`DnsRecordKind`], and return their union.
+    /// The resolution fails if any of the hostnames fails to resolve.
+    ///
+    /// The resolution is valid until the first of the records expires.
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn resolve_service_endpoints_with_metadata(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<Resolution, anyhow::Error> {
+        let lookups = futures::future::try_join_all(
+            definition
+                .hostnames()
+                .iter()
+                .map(|hostname| self.resolve_hostname(hostname, definition.port())),
+        )
+        .await?;
+
+        let valid_until = lookups.iter().map(|(_, valid_until)| *valid_until).min();
+        let mut resolution = Resolution::new(
+            lookups
+                .into_iter()
+                .flat_map(|(endpoints, _)| endpoints)
+                .collect(),
+        );
+        if let Some(valid_until) = valid_until {
+            resolution = resolution.with_valid_until(valid_until);
         }
+
+        Ok(resolution)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A resolver without name servers, which only resolves the localhost names.
+    fn offline_resolver() -> DnsResolver {
+        let (config, opts) = (ResolverConfig::new(), ResolverOpts::default());
+
+        DnsResolver {
+            dns: AsyncResolver::tokio(config.clone(), opts.clone()),
+            config,
+            opts,
+            record_kind: DnsRecordKind::default(),
+        }
+    }
+
+    fn target(name: &str, port: u16) -> (Name, u16) {
+        (Name::from_ascii(name).unwrap(), port)
+    }
+
+    #[tokio::test]
+    async fn srv_targets_failing_to_resolve_are_skipped() {
+        let resolver = offline_resolver();
+
+        let (endpoints, valid_until) = resolver
+            .resolve_srv_targets(
+                "_grpc._tcp.my.service",
+                &[target("localhost.", 5000), target("unknown.invalid.", 5001)],
+            )
+            .await
+            .unwrap();
+        assert!(endpoints.contains(&SocketAddr::from(([127, 0, 0, 1], 5000))));
+        assert!(endpoints.iter().all(|endpoint| endpoint.port() == 5000));
+        assert!(valid_until.is_some());
+
+        let error = resolver
+            .resolve_srv_targets("_grpc._tcp.my.service", &[target("unknown.invalid.", 5001)])
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("_grpc._tcp.my.service"));
+    }
+
+    #[tokio::test]
+    async fn srv_targets_stating_the_service_is_unavailable_are_skipped() {
+        let (endpoints, valid_until) = offline_resolver()
+            .resolve_srv_targets("_grpc._tcp.my.service", &[(Name::root(), 5000)])
+            .await
+            .unwrap();
+
+        assert!(endpoints.is_empty());
+        assert_eq!(None, valid_until);
     }
 }
@@ -0,0 +1,61 @@
+//! Exposes the endpoint changes discovered by the probe as a [`Discover`](tower::discover::Discover).
+
+use futures::Stream;
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc::Receiver;
+use tonic::transport::Endpoint;
+use tower::discover::Change;
+
+/// A stream of the endpoint changes discovered by periodically resolving a
+/// [`ServiceDefinition`](crate::ServiceDefinition), constructed with
+/// [`LoadBalancedChannelBuilder::discover`](crate::LoadBalancedChannelBuilder::discover).
+///
+/// [`EndpointDiscover`] implements [`Discover`](tower::discover::Discover), which decouples
+/// the discovery of the endpoints from their balancing: the changes can be fed to any
+/// balancer once the [`Endpoint`]s have been turned into services.
+///
+/// The probe stops once the [`EndpointDiscover`] is dropped.
+///
+/// ```rust,no_run
+/// #[tokio::main]
+/// async fn main() {
+///     use futures::TryStreamExt;
+///     use ginepro::LoadBalancedChannel;
+///     use tower::discover::Change;
+///
+///     let mut discover = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .discover()
+///         .await
+///         .expect("failed to construct EndpointDiscover");
+///
+///     while let Ok(Some(change)) = discover.try_next().await {
+///         match change {
+///             Change::Insert(address, endpoint) => println!("{} added: {:?}", address, endpoint),
+///             Change::Remove(address) => println!("{} removed", address),
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct EndpointDiscover {
+    changes: Receiver<Change<SocketAddr, Endpoint>>,
+}
+
+impl EndpointDiscover {
+    pub(crate) fn new(changes: Receiver<Change<SocketAddr, Endpoint>>) -> Self {
+        Self { changes }
+    }
+}
+
+impl Stream for EndpointDiscover {
+    type Item = Result<Change<SocketAddr, Endpoint>, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.changes.poll_recv(cx).map(|change| change.map(Ok))
+    }
+}
@@ -0,0 +1,77 @@
+//! Implements a [`LookupService`] reading the endpoints from an environment variable.
+
+use crate::{LookupService, ServiceDefinition};
+use anyhow::Context;
+use std::{collections::HashSet, net::SocketAddr};
+
+/// Implements [`LookupService`] by reading a list of `ip:port` addresses from
+/// an environment variable, e.g. `MY_SERVICE_ENDPOINTS="10.0.0.1:5000, 10.0.0.2:5000"`.
+///
+/// The addresses are separated by commas and/or whitespace, and IPv6 addresses
+/// are written in brackets, e.g. `[::1]:5000`.
+/// The variable is read again on every resolution, so that changes made to it
+/// while the process runs are picked up by the next probe.
+/// Malformed addresses are logged and skipped, while a missing variable fails the
+/// resolution, leaving the current endpoints untouched.
+///
+/// The [`ServiceDefinition`] is ignored, since the addresses carry their own port.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::{EnvLookupService, LoadBalancedChannel};
+///
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .lookup_service(EnvLookupService::new("MY_SERVICE_ENDPOINTS"))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvLookupService {
+    variable: String,
+}
+
+impl EnvLookupService {
+    /// Read the endpoints from the environment variable named `variable`.
+    pub fn new(variable: impl Into<String>) -> Self {
+        Self {
+            variable: variable.into(),
+        }
+    }
+
+    /// Get the name of the environment variable the endpoints are read from.
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+}
+
+#[async_trait::async_trait]
+impl LookupService for EnvLookupService {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let addresses = std::env::var(&self.variable).with_context(|| {
+            format!("failed to read the environment variable {}", self.variable)
+        })?;
+
+        Ok(addresses
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|address| !address.is_empty())
+            .filter_map(|address| match address.parse() {
+                Ok(address) => Some(address),
+                Err(err) => {
+                    tracing::warn!(
+                        "skipping malformed address '{}' in {}: {}",
+                        address,
+                        self.variable,
+                        err
+                    );
+                    None
+                }
+            })
+            .collect())
+    }
+}
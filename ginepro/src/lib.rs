@@ -121,6 +121,30 @@
 //! }
 //! ```
 //!
+//! # Compression
+//! Tonic negotiates compression in the generated clients rather than in the transport,
+//! so it is configured on the client wrapping the [`LoadBalancedChannel`], not on the
+//! channel itself. The `gzip` and `zstd` features of `ginepro` enable the matching
+//! encodings of tonic.
+//!
+//! ```rust
+//! # #[cfg(feature = "gzip")]
+//! # async fn run() {
+//! use ginepro::LoadBalancedChannel;
+//! use shared_proto::pb::tester_client::TesterClient;
+//! use tonic::codec::CompressionEncoding;
+//!
+//! let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+//!     .channel()
+//!     .await
+//!     .expect("failed to construct LoadBalancedChannel");
+//!
+//! let tester_client = TesterClient::new(load_balanced_channel)
+//!     .send_compressed(CompressionEncoding::Gzip)
+//!     .accept_compressed(CompressionEncoding::Gzip);
+//! # }
+//! ```
+//!
 //! # Internals
 //! The tonic [`Channel`](tonic::transport::Channel) exposes the function
 //! [`balance_channel`](tonic::transport::Channel::balance_channel) which returnes a bounded channel through which
@@ -128,12 +152,36 @@
 //! `ginepro` uses this message passing mechanism to report when servers are added and removed.
 
 mod balanced_channel;
+#[cfg(feature = "serde")]
+mod channel_config;
+mod discovery_snapshot;
 mod dns_resolver;
+mod endpoint_discover;
+mod env_lookup_service;
 mod lookup_service;
+mod memoizing_lookup_service;
+mod method_router;
+mod probe_observer;
+mod runtime;
 mod service_definition;
 mod service_probe;
+mod static_lookup_service;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod tls_files;
+
+pub use hickory_resolver;
 
 pub use balanced_channel::*;
+#[cfg(feature = "serde")]
+pub use channel_config::*;
+pub use discovery_snapshot::*;
 pub use dns_resolver::*;
+pub use endpoint_discover::*;
+pub use env_lookup_service::*;
 pub use lookup_service::*;
+pub use memoizing_lookup_service::*;
+pub use method_router::*;
+pub use probe_observer::*;
 pub use service_definition::*;
+pub use static_lookup_service::*;
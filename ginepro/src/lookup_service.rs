@@ -1,9 +1,15 @@
 //! Defines the interface that [`LoadBalancedChannel`](crate::LoadBalancedChannel) requires in order
 //! to resolve all the IP adresses for a given service.
 
-use std::{collections::HashSet, net::SocketAddr};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crate::ServiceDefinition;
+use tower::BoxError;
 
 /// Interface that provides functionality to
 /// acquire a list of ips given a valid host name.
@@ -12,8 +18,208 @@ pub trait LookupService {
     /// Return a list of unique [`SocketAddr`] associated with the provided
     /// [`ServiceDefinition`](crate::ServiceDefinition) containing the `hostname` `port` of the service.
     /// If no ip addresses were resolved, an empty HashSet is returned.
+    ///
+    /// A [`ServiceDefinition`](crate::ServiceDefinition) can carry several
+    /// [`hostnames`](crate::ServiceDefinition::hostnames), whose ips are expected to be merged.
     async fn resolve_service_endpoints(
         &self,
         definition: &ServiceDefinition,
     ) -> Result<HashSet<SocketAddr>, anyhow::Error>;
+
+    /// Resolve the endpoints of the [`ServiceDefinition`] as
+    /// [`resolve_service_endpoints`](LookupService::resolve_service_endpoints) does,
+    /// along with hints about the freshness of the result. This is what the probe calls.
+    ///
+    /// The default implementation returns no hint, so that the probe keeps its fixed interval,
+    /// and no validity window.
+    /// A lookup service backed by a registry can override it to drive the probe cadence,
+    /// e.g. returning a short [`Resolution::refresh_after`] when the registry supports
+    /// long polling, as the next resolution then blocks until the endpoints change.
+    async fn resolve_service_endpoints_with_metadata(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<Resolution, anyhow::Error> {
+        self.resolve_service_endpoints(definition)
+            .await
+            .map(Resolution::new)
+    }
+}
+
+/// The endpoints resolved by a [`LookupService`], with hints about their freshness.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Resolution {
+    /// The resolved endpoints.
+    pub endpoints: HashSet<SocketAddr>,
+    /// How long to wait before resolving the endpoints again, instead of the probe interval.
+    ///
+    /// When several service definitions are resolved by a probe, the shortest hint applies.
+    pub refresh_after: Option<Duration>,
+    /// When the resolved endpoints become stale, e.g. as the TTL of the DNS records expires.
+    ///
+    /// When several service definitions are resolved by a probe, the earliest instant applies.
+    pub valid_until: Option<Instant>,
+}
+
+impl Resolution {
+    /// Wrap `endpoints` without any hint.
+    pub fn new(endpoints: HashSet<SocketAddr>) -> Self {
+        Self {
+            endpoints,
+            refresh_after: None,
+            valid_until: None,
+        }
+    }
+
+    /// Ask the probe to resolve the endpoints again after `refresh_after`.
+    pub fn with_refresh_after(self, refresh_after: Duration) -> Self {
+        Self {
+            refresh_after: Some(refresh_after),
+            ..self
+        }
+    }
+
+    /// Record that the endpoints become stale at `valid_until`.
+    pub fn with_valid_until(self, valid_until: Instant) -> Self {
+        Self {
+            valid_until: Some(valid_until),
+            ..self
+        }
+    }
+}
+
+/// Share a [`LookupService`] across channels.
+#[async_trait::async_trait]
+impl<T: LookupService + ?Sized + Send + Sync> LookupService for Arc<T> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        (**self).resolve_service_endpoints(definition).await
+    }
+
+    async fn resolve_service_endpoints_with_metadata(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<Resolution, anyhow::Error> {
+        (**self)
+            .resolve_service_endpoints_with_metadata(definition)
+            .await
+    }
+}
+
+/// Use a boxed [`LookupService`], e.g. to pick the implementation at runtime.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::{DnsResolver, LoadBalancedChannel, LookupService};
+///
+///     let lookup_service: Box<dyn LookupService + Send + Sync> = Box::new(
+///         DnsResolver::from_system_config()
+///             .await
+///             .expect("failed to read the system dns configuration"),
+///     );
+///
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .lookup_service(lookup_service)
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+/// }
+/// ```
+#[async_trait::async_trait]
+impl<T: LookupService + ?Sized + Send + Sync> LookupService for Box<T> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        (**self).resolve_service_endpoints(definition).await
+    }
+
+    async fn resolve_service_endpoints_with_metadata(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<Resolution, anyhow::Error> {
+        (**self)
+            .resolve_service_endpoints_with_metadata(definition)
+            .await
+    }
+}
+
+/// Interface equivalent to [`LookupService`] for implementers that do not use `anyhow`,
+/// failing with a boxed standard error instead.
+///
+/// Wrap it in a [`BoxErrorLookup`] to use it as a [`LookupService`].
+/// The errors returned by [`LoadBalancedChannelBuilder`](crate::LoadBalancedChannelBuilder)
+/// are still `anyhow::Error`s, which convert into a boxed standard error with `Into`.
+///
+/// ```rust
+/// use ginepro::{BoxErrorLookup, BoxErrorLookupService, LoadBalancedChannel, ServiceDefinition};
+/// use std::collections::HashSet;
+/// use std::net::SocketAddr;
+///
+/// struct MyLookupService;
+///
+/// #[async_trait::async_trait]
+/// impl BoxErrorLookupService for MyLookupService {
+///     async fn resolve_service_endpoints(
+///         &self,
+///         _definition: &ServiceDefinition,
+///     ) -> Result<HashSet<SocketAddr>, Box<dyn std::error::Error + Send + Sync>> {
+///         let address: SocketAddr = "127.0.0.1:5000".parse()?;
+///         Ok(HashSet::from([address]))
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .lookup_service(BoxErrorLookup::new(MyLookupService))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait BoxErrorLookupService {
+    /// Return a list of unique [`SocketAddr`] associated with the provided
+    /// [`ServiceDefinition`](crate::ServiceDefinition),
+    /// as [`LookupService::resolve_service_endpoints`] does.
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, BoxError>;
+}
+
+/// Adapts a [`BoxErrorLookupService`] into a [`LookupService`].
+///
+/// The errors are converted to an [`anyhow::Error`] keeping their message and sources.
+pub struct BoxErrorLookup<T> {
+    inner: T,
+}
+
+impl<T> BoxErrorLookup<T> {
+    /// Use `inner` as a [`LookupService`].
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get the wrapped [`BoxErrorLookupService`].
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: BoxErrorLookupService + Send + Sync> LookupService for BoxErrorLookup<T> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.inner
+            .resolve_service_endpoints(definition)
+            .await
+            .map_err(anyhow::Error::from_boxed)
+    }
 }
@@ -0,0 +1,100 @@
+//! Implements a [`LookupService`] that reduces the rate of resolutions of another one.
+
+use crate::{LookupService, Resolution, ServiceDefinition};
+use std::{collections::HashSet, net::SocketAddr, sync::Mutex};
+use tokio::time::{Duration, Instant};
+
+/// Wraps a [`LookupService`] and returns the last successful resolution of a
+/// [`ServiceDefinition`] for `ttl`, without resolving it again.
+///
+/// This reduces the load on DNS when the probe interval is short and the records are stable.
+/// Failed resolutions are not memoized, and a memoized result is never returned once its
+/// `ttl` has elapsed: this is a rate reducer, not a fallback for a failing resolver.
+///
+/// The whole [`Resolution`] is memoized, hints included. A memoized refresh hint is
+/// lengthened to the time left until the resolution expires, since resolving earlier
+/// would only return the memoized resolution again.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::{DnsResolver, LoadBalancedChannel, MemoizingLookupService};
+///     use std::time::Duration;
+///
+///     let resolver = DnsResolver::from_system_config()
+///         .await
+///         .expect("failed to read the system dns configuration");
+///
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .dns_probe_interval(Duration::from_millis(100))
+///         .lookup_service(MemoizingLookupService::new(resolver, Duration::from_secs(1)))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+/// }
+/// ```
+pub struct MemoizingLookupService<T> {
+    inner: T,
+    ttl: Duration,
+    /// The last successful resolution of every service definition, and when it was made.
+    memo: Mutex<Vec<(ServiceDefinition, Instant, Resolution)>>,
+}
+
+impl<T> MemoizingLookupService<T> {
+    /// Memoize the resolutions of `inner` for `ttl`.
+    pub fn new(inner: T, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            memo: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get the wrapped [`LookupService`].
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: LookupService + Send + Sync> LookupService for MemoizingLookupService<T> {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.resolve_service_endpoints_with_metadata(definition)
+            .await
+            .map(|resolution| resolution.endpoints)
+    }
+
+    async fn resolve_service_endpoints_with_metadata(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<Resolution, anyhow::Error> {
+        {
+            let memo = self.memo.lock().expect("memo lock poisoned");
+            let memoized = memo.iter().find(|(memoized, resolved_at, _)| {
+                memoized == definition && resolved_at.elapsed() < self.ttl
+            });
+            if let Some((_, resolved_at, resolution)) = memoized {
+                let expires_in = self.ttl.saturating_sub(resolved_at.elapsed());
+                let mut resolution = resolution.clone();
+                resolution.refresh_after = resolution
+                    .refresh_after
+                    .map(|refresh_after| refresh_after.max(expires_in));
+                return Ok(resolution);
+            }
+        }
+
+        let resolution = self
+            .inner
+            .resolve_service_endpoints_with_metadata(definition)
+            .await?;
+
+        let mut memo = self.memo.lock().expect("memo lock poisoned");
+        memo.retain(|(memoized, _, _)| memoized != definition);
+        memo.push((definition.clone(), Instant::now(), resolution.clone()));
+
+        Ok(resolution)
+    }
+}
@@ -0,0 +1,87 @@
+//! Routes the requests of a single logical channel to several pools of endpoints,
+//! based on the gRPC method they call.
+
+use crate::LoadBalancedChannel;
+use http::Request;
+use std::task::{Context, Poll};
+use tonic::body::BoxBody;
+use tower::{util::Oneshot, BoxError, Service, ServiceExt};
+
+/// Routes every request to the [`LoadBalancedChannel`] registered for the longest prefix
+/// of its gRPC method path, e.g. `/my.package.Writes/`, or to the default channel.
+///
+/// Every pool is a [`LoadBalancedChannel`] of its own, with its own [`LookupService`](crate::LookupService)
+/// and settings, e.g. to split the read and the write traffic of a service across backend
+/// pools discovered under different names.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::{LoadBalancedChannel, MethodRouter};
+///     use shared_proto::pb::tester_client::TesterClient;
+///
+///     let reads = LoadBalancedChannel::builder(("reads.my.hostname", 5000))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+///     let writes = LoadBalancedChannel::builder(("writes.my.hostname", 5000))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+///
+///     let router = MethodRouter::new(reads).route("/my.package.Writes/", writes);
+///     let client = TesterClient::new(router);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MethodRouter {
+    default: LoadBalancedChannel,
+    /// The routes, sorted by decreasing prefix length so that the longest prefix matches.
+    routes: Vec<(String, LoadBalancedChannel)>,
+}
+
+impl MethodRouter {
+    /// Route every request to `default` until routes are added.
+    pub fn new(default: LoadBalancedChannel) -> Self {
+        Self {
+            default,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Route the requests whose method path starts with `prefix` to `channel`,
+    /// replacing the channel registered for the same prefix, if any.
+    pub fn route(mut self, prefix: impl Into<String>, channel: LoadBalancedChannel) -> Self {
+        let prefix = prefix.into();
+        self.routes.retain(|(route, _)| *route != prefix);
+        self.routes.push((prefix, channel));
+        self.routes
+            .sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    /// Get the channel the requests to `path` are routed to.
+    pub fn channel_for(&self, path: &str) -> &LoadBalancedChannel {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map_or(&self.default, |(_, channel)| channel)
+    }
+}
+
+impl Service<Request<BoxBody>> for MethodRouter {
+    type Response = http::Response<BoxBody>;
+    type Error = BoxError;
+    type Future = Oneshot<LoadBalancedChannel, Request<BoxBody>>;
+
+    /// The pool is only known once the request is, so readiness is awaited by the call.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<BoxBody>) -> Self::Future {
+        self.channel_for(request.uri().path())
+            .clone()
+            .oneshot(request)
+    }
+}
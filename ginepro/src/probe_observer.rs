@@ -0,0 +1,75 @@
+//! Defines the hooks through which a [`LoadBalancedChannel`](crate::LoadBalancedChannel)
+//! reports on the work of its probe.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Receives notifications about the probe of a [`LoadBalancedChannel`](crate::LoadBalancedChannel),
+/// e.g. to record metrics, set with
+/// [`LoadBalancedChannelBuilder::probe_observer`](crate::LoadBalancedChannelBuilder::probe_observer).
+///
+/// Every notification has a no-op default implementation, so that an observer only implements
+/// the ones it is interested in. Notifications are delivered from the probe task and are
+/// expected to return quickly.
+///
+/// ```rust
+/// use ginepro::ProbeObserver;
+/// use std::time::Duration;
+///
+/// struct LogSlowResolutions;
+///
+/// impl ProbeObserver for LogSlowResolutions {
+///     fn on_resolution(&self, duration: Duration, succeeded: bool) {
+///         if duration > Duration::from_secs(1) {
+///             println!("slow resolution ({:?}), succeeded: {}", duration, succeeded);
+///         }
+///     }
+/// }
+/// ```
+pub trait ProbeObserver: Send + Sync {
+    /// Called after every resolution of the service definitions with the time it took,
+    /// whether it `succeeded` or not.
+    fn on_resolution(&self, _duration: Duration, _succeeded: bool) {}
+
+    /// Called once, after the first successful resolution of the service definitions, with
+    /// the time `elapsed` since the channel was constructed and the number of `endpoints` found.
+    ///
+    /// This is the push-based counterpart of
+    /// [`LoadBalancedChannel::first_resolution`](crate::LoadBalancedChannel::first_resolution),
+    /// and `endpoints` can be `0` if the first successful resolution found none.
+    fn on_first_resolution(&self, _elapsed: Duration, _endpoints: usize) {}
+
+    /// Called when the number of `endpoints` committed by a probe drops below the `minimum` set with
+    /// [`LoadBalancedChannelBuilder::min_healthy_warn`](crate::LoadBalancedChannelBuilder::min_healthy_warn),
+    /// or when the first probe commits fewer endpoints than that.
+    fn on_endpoints_below_minimum(&self, _endpoints: usize, _minimum: usize) {}
+
+    /// Called whenever endpoint changes are reported to the balancer, with the exact
+    /// addresses `added` to and `removed` from the channel, in ascending order.
+    ///
+    /// A probe usually reports its changes at once, but some settings report them in
+    /// several steps, e.g. [`LoadBalancedChannelBuilder::zero_downtime_cutover`](crate::LoadBalancedChannelBuilder::zero_downtime_cutover)
+    /// adds the new endpoints before removing the old ones. It is not called when nothing changed.
+    fn on_endpoints_changed(&self, _added: &[SocketAddr], _removed: &[SocketAddr]) {}
+
+    /// Called after every successful probe with the [`ProbeChurn`] it caused, including the
+    /// probes that changed nothing, e.g. to quantify the connection churn and tune the probe
+    /// interval or [`LoadBalancedChannelBuilder::removal_confirmations`](crate::LoadBalancedChannelBuilder::removal_confirmations).
+    fn on_probe_churn(&self, _churn: ProbeChurn) {}
+}
+
+/// How the endpoints of a channel changed over a probe, as reported to
+/// [`ProbeObserver::on_probe_churn`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ProbeChurn {
+    /// The endpoints kept by the probe, whose connections are reused.
+    pub stable: usize,
+    /// The endpoints added by the probe.
+    pub added: usize,
+    /// The endpoints removed by the probe.
+    pub removed: usize,
+    /// The endpoints among the `added` ones that had been removed by one of the few
+    /// previous probes, i.e. whose connections were torn down only to be opened again.
+    pub readded: usize,
+}
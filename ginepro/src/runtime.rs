@@ -0,0 +1,45 @@
+//! The seam between ginepro and the async runtime it spawns the probe and sleeps on.
+//!
+//! Only tokio is implemented, but the probe loop and the construction of the channel
+//! go through [`Runtime`] rather than calling tokio directly, so that another runtime
+//! can be plugged in without touching them. Timeouts still use `tokio::time`.
+
+use std::future::Future;
+use std::time::Duration;
+
+/// Spawns the tasks and provides the timers of ginepro.
+pub(crate) trait Runtime {
+    /// The future returned by [`Runtime::sleep`].
+    type Sleep: Future<Output = ()> + Send;
+
+    /// Run `future` in the background, detached from the caller.
+    fn spawn<F>(future: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static;
+
+    /// Complete after `duration` has elapsed.
+    fn sleep(duration: Duration) -> Self::Sleep;
+}
+
+/// The runtime ginepro runs on.
+pub(crate) type DefaultRuntime = TokioRuntime;
+
+/// Runs on the ambient tokio runtime, whose clock can be paused in tests.
+pub(crate) struct TokioRuntime;
+
+impl Runtime for TokioRuntime {
+    type Sleep = tokio::time::Sleep;
+
+    fn spawn<F>(future: F)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        tokio::spawn(future);
+    }
+
+    fn sleep(duration: Duration) -> Self::Sleep {
+        tokio::time::sleep(duration)
+    }
+}
@@ -2,10 +2,18 @@ use anyhow::Context;
 
 /// Defines a gRPC service with a `hostname` and a `port`.
 /// The hostname will be resolved to the concrete ips of the service servers.
-#[derive(Debug)]
+///
+/// A service exposed under several hostnames (e.g. blue/green or primary/DR) can be
+/// defined with [`ServiceDefinition::from_hostnames`], in which case the ips of all
+/// the hostnames are treated as a single pool.
+///
+/// Two definitions are equal, and hash the same, when they have the same hostnames
+/// in the same order and the same port.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ServiceDefinition {
-    /// The hostname of the service.
-    hostname: String,
+    /// The hostnames of the service, the first one being the primary hostname.
+    hostnames: Vec<String>,
     /// The service port.
     port: u16,
 }
@@ -13,20 +21,96 @@ pub struct ServiceDefinition {
 impl ServiceDefinition {
     /// Create a [`ServiceDefinition`] from a valid `hostname` and `port`.
     ///
-    /// This function will fail is the `hostname` is not a valid domain name.
+    /// This function will fail is the `hostname` is not a valid domain name,
+    /// if the `hostname` is empty or if the `port` is `0`, which can not be connected to.
+    ///
+    /// The `hostname` is a sequence of `.` separated labels, where each label:
+    /// * is made of ASCII letters, digits, `-` and `_`, or is the `*` wildcard;
+    /// * does not start with `-`;
+    /// * is at most 63 bytes long.
+    ///
+    /// This covers fully qualified names with a trailing dot (e.g. `my.service.local.`)
+    /// and SRV-style names with underscore prefixed labels (e.g. `_grpc._tcp.my.service.local.`).
+    /// Empty labels (e.g. `my..service`), whitespace, ports (e.g. `my.service:5000`) and
+    /// non-ASCII characters are rejected.
+    ///
+    /// ```
+    /// use ginepro::ServiceDefinition;
+    ///
+    /// assert!(ServiceDefinition::from_parts("my.service.local.", 5000).is_ok());
+    /// assert!(ServiceDefinition::from_parts("_grpc._tcp.my.service.local.", 5000).is_ok());
+    /// assert!(ServiceDefinition::from_parts("my..service", 5000).is_err());
+    /// assert!(ServiceDefinition::from_parts("my.service", 0).is_err());
+    /// ```
     pub fn from_parts<T: ToString>(hostname: T, port: u16) -> Result<Self, anyhow::Error> {
-        let hostname = hostname.to_string();
+        Self::from_hostnames([hostname.to_string()], port)
+    }
+
+    /// Create a [`ServiceDefinition`] for a service exposed under several valid `hostnames`
+    /// sharing the same `port`.
+    ///
+    /// The first hostname is the primary one, returned by [`ServiceDefinition::hostname`].
+    /// Every hostname is validated as in [`ServiceDefinition::from_parts`], and this function
+    /// will also fail if no hostname is provided. Duplicated hostnames are ignored.
+    ///
+    /// ```
+    /// use ginepro::ServiceDefinition;
+    ///
+    /// let sd = ServiceDefinition::from_hostnames(
+    ///     ["blue.my.service".to_string(), "green.my.service".to_string()],
+    ///     5000,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(sd.hostname(), "blue.my.service");
+    /// assert_eq!(sd.hostnames(), ["blue.my.service", "green.my.service"]);
+    /// ```
+    pub fn from_hostnames(
+        hostnames: impl IntoIterator<Item = String>,
+        port: u16,
+    ) -> Result<Self, anyhow::Error> {
+        let mut unique_hostnames: Vec<String> = Vec::new();
 
-        hickory_resolver::Name::from_ascii(&hostname)
-            .map_err(anyhow::Error::from)
-            .context("invalid 'hostname'")?;
+        for hostname in hostnames {
+            if hostname.is_empty() {
+                anyhow::bail!("invalid 'hostname': the hostname is empty");
+            }
 
-        Ok(Self { hostname, port })
+            hickory_resolver::Name::from_ascii(&hostname)
+                .map_err(anyhow::Error::from)
+                .context("invalid 'hostname'")?;
+
+            if !unique_hostnames.contains(&hostname) {
+                unique_hostnames.push(hostname);
+            }
+        }
+
+        let Some(primary_hostname) = unique_hostnames.first() else {
+            anyhow::bail!("invalid 'hostname': no hostname provided");
+        };
+
+        if port == 0 {
+            anyhow::bail!(
+                "invalid 'port' for '{}': port 0 can not be connected to",
+                primary_hostname
+            );
+        }
+
+        Ok(Self {
+            hostnames: unique_hostnames,
+            port,
+        })
     }
 
     /// Get the `hostname` part of a `ServiceDefinition`.
+    ///
+    /// When the service has several hostnames this is the primary one.
     pub fn hostname(&self) -> &str {
-        &self.hostname
+        &self.hostnames[0]
+    }
+
+    /// Get all the hostnames of a `ServiceDefinition`, starting with the primary one.
+    pub fn hostnames(&self) -> &[String] {
+        &self.hostnames
     }
 
     /// Get the `port` part of a `ServiceDefinition`.
@@ -61,6 +145,59 @@ impl TryFrom<(String, u16)> for ServiceDefinition {
     }
 }
 
+/// Take the host and the port of a `Uri`, e.g. `http://my.service:5000`.
+///
+/// The port defaults to `80` for `http` and to `443` for `https`, and the conversion fails
+/// when it is missing from a uri with another scheme or without a scheme. The host is
+/// validated as in [`ServiceDefinition::from_parts`]: IPv6 hosts (e.g. `http://[::1]:5000`)
+/// are rejected, since they are not names to resolve, and fixed addresses are better served by
+/// [`LoadBalancedChannel::from_endpoints`](crate::LoadBalancedChannel::from_endpoints).
+///
+/// ```
+/// use ginepro::ServiceDefinition;
+///
+/// let uri: http::Uri = "https://my.service.local".parse().unwrap();
+/// let sd = ServiceDefinition::try_from(uri).unwrap();
+/// assert_eq!(sd.hostname(), "my.service.local");
+/// assert_eq!(sd.port(), 443);
+/// ```
+impl TryFrom<&http::Uri> for ServiceDefinition {
+    type Error = anyhow::Error;
+
+    fn try_from(uri: &http::Uri) -> Result<Self, Self::Error> {
+        let host = uri
+            .host()
+            .with_context(|| format!("invalid uri '{}': the host is missing", uri))?;
+        if host.starts_with('[') {
+            anyhow::bail!(
+                "invalid uri '{}': the host is an IPv6 address rather than a hostname",
+                uri
+            );
+        }
+
+        let port = match (uri.port_u16(), uri.scheme_str()) {
+            (Some(port), _) => port,
+            (None, Some("http")) => 80,
+            (None, Some("https")) => 443,
+            (None, _) => anyhow::bail!(
+                "invalid uri '{}': the port is missing and can not be derived from the scheme",
+                uri
+            ),
+        };
+
+        Self::from_parts(host, port)
+    }
+}
+
+/// Take the host and the port of a `Uri`, as `TryFrom<&Uri>` does.
+impl TryFrom<http::Uri> for ServiceDefinition {
+    type Error = anyhow::Error;
+
+    fn try_from(uri: http::Uri) -> Result<Self, Self::Error> {
+        Self::try_from(&uri)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -72,6 +209,18 @@ mod test {
         }
     }
 
+    prop_compose! {
+        fn fqdn_hostname()(s in "([a-z0-9][a-z0-9-]{0,10}\\.){1,4}[a-z]{2,6}\\.") -> String {
+            s
+        }
+    }
+
+    prop_compose! {
+        fn srv_hostname()(s in "_[a-z0-9-]{1,10}\\._(tcp|udp)(\\.[a-z0-9][a-z0-9-]{0,10}){1,4}\\.?") -> String {
+            s
+        }
+    }
+
     prop_compose! {
         fn invalid_hostname()(s in "[^\\a-z.0-9*A-Z]+") -> String {
             s
@@ -84,9 +233,93 @@ mod test {
                 proptest::prop_assert!(ServiceDefinition::from_parts(hostname, 5000).is_ok());
         }
 
+        #[test]
+        fn fqdn_hostname_shall_succeed(hostname in fqdn_hostname()) {
+                proptest::prop_assert!(ServiceDefinition::from_parts(hostname, 5000).is_ok());
+        }
+
+        #[test]
+        fn srv_hostname_shall_succeed(hostname in srv_hostname()) {
+                proptest::prop_assert!(ServiceDefinition::from_parts(hostname, 5000).is_ok());
+        }
+
         #[test]
         fn invalid_hostname_shall_fail(hostname in invalid_hostname()) {
                 proptest::prop_assert!(ServiceDefinition::from_parts(hostname, 5000).is_err());
         }
+
+        #[test]
+        fn port_zero_shall_fail(hostname in valid_hostname()) {
+                proptest::prop_assert!(ServiceDefinition::from_parts(hostname, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_hostname_shall_fail() {
+        assert!(ServiceDefinition::from_parts("", 5000).is_err());
+    }
+
+    #[test]
+    fn no_hostnames_shall_fail() {
+        assert!(ServiceDefinition::from_hostnames([], 5000).is_err());
+    }
+
+    #[test]
+    fn any_invalid_hostname_shall_fail() {
+        assert!(ServiceDefinition::from_hostnames(
+            ["valid".to_string(), "in valid".to_string()],
+            5000
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn equal_definitions_shall_be_the_same_map_key() {
+        let mut channels = std::collections::HashMap::new();
+        channels.insert(
+            ServiceDefinition::from_parts("my.service", 5000).unwrap(),
+            1,
+        );
+        channels.insert(
+            ServiceDefinition::try_from(("my.service", 5000)).unwrap(),
+            2,
+        );
+        channels.insert(
+            ServiceDefinition::from_parts("my.service", 5001).unwrap(),
+            3,
+        );
+
+        assert_eq!(2, channels.len());
+        assert_eq!(
+            Some(&2),
+            channels.get(&ServiceDefinition::from_parts("my.service", 5000).unwrap())
+        );
+    }
+
+    fn from_uri(uri: &str) -> Result<ServiceDefinition, anyhow::Error> {
+        ServiceDefinition::try_from(uri.parse::<http::Uri>().unwrap())
+    }
+
+    #[test]
+    fn uris_with_a_port_shall_succeed() {
+        let sd = from_uri("http://my.service:5000/ignored/path").unwrap();
+        assert_eq!(("my.service", 5000), (sd.hostname(), sd.port()));
+
+        let sd = from_uri("my.service:5000").unwrap();
+        assert_eq!(("my.service", 5000), (sd.hostname(), sd.port()));
+    }
+
+    #[test]
+    fn missing_ports_shall_default_to_the_port_of_the_scheme() {
+        assert_eq!(80, from_uri("http://my.service").unwrap().port());
+        assert_eq!(443, from_uri("https://my.service").unwrap().port());
+        assert!(from_uri("grpc://my.service").is_err());
+        assert!(from_uri("/no/authority").is_err());
+    }
+
+    #[test]
+    fn ipv6_hosts_shall_fail() {
+        let error = from_uri("http://[::1]:5000").unwrap_err();
+        assert!(error.to_string().contains("IPv6"));
     }
 }
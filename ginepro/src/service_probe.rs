@@ -1,20 +1,475 @@
-use crate::{LookupService, ServiceDefinition};
-use std::collections::HashSet;
+use crate::runtime::{DefaultRuntime, Runtime};
+use crate::{
+    ChangeOrdering, ChangeReportThreshold, DiscoverySnapshot, FullBufferPolicy, LookupService,
+    PartialResultPolicy, ProbeChurn, ProbeObserver, Resolution, ServiceDefinition,
+};
+use anyhow::Context;
+use futures::FutureExt;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
-use tokio::sync::mpsc::Sender;
-use tonic::transport::{channel::Endpoint, ClientTlsConfig};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::SystemTime;
+use tokio::net::TcpStream;
+use tokio::sync::{
+    mpsc::{error::TrySendError, Sender},
+    watch,
+};
+use tonic::transport::{
+    channel::{Channel, Endpoint},
+    ClientTlsConfig,
+};
 use tower::discover::Change;
 
+// The time allowed for the TCP or gRPC precheck of a new endpoint
+// when no connection timeout has been configured.
+static DEFAULT_PRECHECK_TIMEOUT: tokio::time::Duration = tokio::time::Duration::from_secs(1);
+
+// The shortest delay between two probes when a probe is requested early.
+static MIN_REPROBE_DELAY: tokio::time::Duration = tokio::time::Duration::from_secs(1);
+
+// How many probes a removed endpoint is remembered for, to tell when it is added again.
+static CHURN_MEMORY_PROBES: u64 = 10;
+
+// The gRPC method called by the gRPC precheck.
+static GRPC_PRECHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+/// A fallible customization applied to every endpoint built by the probe,
+/// along with the address it is built for.
+pub(crate) type EndpointLayerFn =
+    Box<dyn Fn(SocketAddr, Endpoint) -> Result<Endpoint, anyhow::Error> + Send + Sync>;
+
+/// A callback fired by the probe on a transition of the endpoint set.
+pub(crate) type EndpointsCallback = Box<dyn Fn() + Send + Sync>;
+
+/// A callback fired by the probe after a number of consecutive failed probes.
+pub(crate) type FailuresCallback = Box<dyn Fn() + Send + Sync>;
+
+/// Builds the uri of the endpoint of every resolved address, instead of the probe.
+pub(crate) type EndpointUriFn = Box<dyn Fn(&SocketAddr) -> http::Uri + Send + Sync>;
+
+/// A rewrite applied to every resolved address before an endpoint is built for it.
+pub(crate) type AddressRewriteFn = Box<dyn Fn(SocketAddr) -> SocketAddr + Send + Sync>;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ProbeError {
-    #[error("Failed to resolve ServiceDefinition")]
-    ResolveServiceDefinition(#[source] anyhow::Error),
+    #[error("Failed to resolve ServiceDefinition '{hostname}:{port}'")]
+    ResolveServiceDefinition {
+        /// The primary hostname of the service definition.
+        hostname: String,
+        port: u16,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("Failed to build an Endpoint from a resolved address")]
+    BuildEndpoint(#[source] anyhow::Error),
     #[error("Changeset sender closed")]
     ChangesetSenderClosed(#[source] anyhow::Error),
+    #[error("Resolved {resolved} endpoints, more than the limit of {limit}")]
+    TooManyEndpoints { resolved: usize, limit: usize },
+}
+
+/// State shared between a [`GrpcServiceProbe`] and the
+/// [`LoadBalancedChannel`](crate::LoadBalancedChannel) it reports to.
+#[derive(Debug)]
+pub(crate) struct ProbeState {
+    /// The service definition the channel has been constructed with.
+    service_definition: ServiceDefinition,
+    /// The service definitions whose endpoints are merged into a single set.
+    service_definitions: RwLock<Vec<ServiceDefinition>>,
+    /// The endpoints reported to tonic, with a dedicated channel that is only
+    /// created once it is asked for.
+    endpoints: Mutex<HashMap<SocketAddr, (Endpoint, Option<Channel>)>>,
+    /// Set once the first successful probe has been committed.
+    resolved: watch::Sender<bool>,
+    /// Set while the service definitions are being resolved.
+    probing: AtomicBool,
+    /// The tls configuration applied to new endpoints, if tls is enabled.
+    tls_config: RwLock<Option<ClientTlsConfig>>,
+    /// The domain name the tls certificates of the endpoints are verified against,
+    /// unless the domain name of the tls configuration is left untouched.
+    tls_domain_name: Option<String>,
+    /// When the last probe completed, and why it failed if it did.
+    last_probe: Mutex<Option<(SystemTime, Option<String>)>>,
+    /// The number of non-empty changesets reported to tonic.
+    generation: AtomicU64,
+    /// How many endpoints accepted a connection while the channel was constructed.
+    ready_at_startup: OnceLock<usize>,
+    /// Wakes the probe up before the end of its interval.
+    reprobe: tokio::sync::Notify,
+}
+
+impl ProbeState {
+    pub(crate) fn new(service_definition: ServiceDefinition) -> Self {
+        Self {
+            tls_domain_name: Some(service_definition.hostname().to_string()),
+            service_definitions: RwLock::new(vec![service_definition.clone()]),
+            service_definition,
+            endpoints: Mutex::new(HashMap::new()),
+            resolved: watch::Sender::new(false),
+            probing: AtomicBool::new(false),
+            tls_config: RwLock::new(None),
+            last_probe: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            ready_at_startup: OnceLock::new(),
+            reprobe: tokio::sync::Notify::new(),
+        }
+    }
+
+    /// Ask the probe to run again without waiting for the end of its interval.
+    pub(crate) fn request_probe(&self) {
+        self.reprobe.notify_one();
+    }
+
+    /// Verify the endpoints against `tls_domain_name` rather than the hostname of
+    /// the service definition.
+    pub(crate) fn with_tls_domain_name(self, tls_domain_name: String) -> Self {
+        Self {
+            tls_domain_name: Some(tls_domain_name),
+            ..self
+        }
+    }
+
+    /// Leave the domain name of the tls configurations untouched, rather than
+    /// verifying the endpoints against the hostname of the service definition.
+    pub(crate) fn without_tls_domain_name_override(self) -> Self {
+        Self {
+            tls_domain_name: None,
+            ..self
+        }
+    }
+
+    /// Enable tls for the endpoints built from now on, using `tls_config`.
+    pub(crate) fn set_tls_config(&self, tls_config: ClientTlsConfig) {
+        // Since we resolve the hostname to an IP, which is not a valid DNS name,
+        // we have to set the hostname explicitly on the tls config,
+        // otherwise the IP will be set as the domain name and tls handshake will fail.
+        let tls_config = match &self.tls_domain_name {
+            Some(tls_domain_name) => tls_config.domain_name(tls_domain_name.clone()),
+            None => tls_config,
+        };
+
+        *self.tls_config.write().expect("tls config lock poisoned") = Some(tls_config);
+    }
+
+    /// Whether new endpoints are built with a tls configuration.
+    pub(crate) fn is_tls_enabled(&self) -> bool {
+        self.tls_config
+            .read()
+            .expect("tls config lock poisoned")
+            .is_some()
+    }
+
+    /// The tls configuration to apply to new endpoints, if tls is enabled.
+    pub(crate) fn tls_config(&self) -> Option<ClientTlsConfig> {
+        self.tls_config
+            .read()
+            .expect("tls config lock poisoned")
+            .clone()
+    }
+
+    /// The service definition the channel has been constructed with.
+    pub(crate) fn service_definition(&self) -> &ServiceDefinition {
+        &self.service_definition
+    }
+
+    /// Snapshot of the currently registered service definitions.
+    pub(crate) fn service_definitions(&self) -> Vec<ServiceDefinition> {
+        self.service_definitions
+            .read()
+            .expect("service definitions lock poisoned")
+            .clone()
+    }
+
+    /// Register `service_definition`, returns `false` if it was already registered.
+    pub(crate) fn add_service_definition(&self, service_definition: ServiceDefinition) -> bool {
+        let mut service_definitions = self
+            .service_definitions
+            .write()
+            .expect("service definitions lock poisoned");
+
+        if service_definitions.contains(&service_definition) {
+            return false;
+        }
+        service_definitions.push(service_definition);
+
+        true
+    }
+
+    /// Unregister `service_definition`, returns `false` if it was not registered.
+    pub(crate) fn remove_service_definition(&self, service_definition: &ServiceDefinition) -> bool {
+        let mut service_definitions = self
+            .service_definitions
+            .write()
+            .expect("service definitions lock poisoned");
+        let len = service_definitions.len();
+        service_definitions.retain(|definition| definition != service_definition);
+
+        service_definitions.len() != len
+    }
+
+    /// Get a dedicated channel for every endpoint reported to tonic,
+    /// lazily connecting the ones that have not been asked for before.
+    pub(crate) fn endpoint_channels(&self) -> Vec<(SocketAddr, Channel)> {
+        self.endpoints
+            .lock()
+            .expect("endpoints lock poisoned")
+            .iter_mut()
+            .map(|(address, (endpoint, channel))| {
+                let channel = channel.get_or_insert_with(|| endpoint.connect_lazy());
+                (*address, channel.clone())
+            })
+            .collect()
+    }
+
+    /// Get the dedicated channel of the endpoint at `address`, if it has been reported to tonic.
+    pub(crate) fn endpoint_channel(&self, address: &SocketAddr) -> Option<Channel> {
+        self.endpoints
+            .lock()
+            .expect("endpoints lock poisoned")
+            .get_mut(address)
+            .map(|(endpoint, channel)| {
+                channel
+                    .get_or_insert_with(|| endpoint.connect_lazy())
+                    .clone()
+            })
+    }
+
+    /// The endpoints currently reported to tonic.
+    pub(crate) fn endpoints(&self) -> Vec<(SocketAddr, Endpoint)> {
+        self.endpoints
+            .lock()
+            .expect("endpoints lock poisoned")
+            .iter()
+            .map(|(address, (endpoint, _))| (*address, endpoint.clone()))
+            .collect()
+    }
+
+    /// Record how many endpoints accepted a connection while the channel was constructed.
+    /// Flag the endpoints as resolved without any probe, when there is nothing to resolve.
+    pub(crate) fn mark_resolved(&self) {
+        self.resolved.send_replace(true);
+    }
+
+    pub(crate) fn set_ready_at_startup(&self, ready: usize) {
+        let _ = self.ready_at_startup.set(ready);
+    }
+
+    /// How many endpoints accepted a connection while the channel was constructed,
+    /// if they were connected to.
+    pub(crate) fn ready_at_startup(&self) -> Option<usize> {
+        self.ready_at_startup.get().copied()
+    }
+
+    /// An upper bound of the number of connections held by the channel: one for every endpoint
+    /// reported to tonic, and one for every dedicated channel that has been asked for.
+    pub(crate) fn open_connection_count(&self) -> usize {
+        self.endpoints
+            .lock()
+            .expect("endpoints lock poisoned")
+            .values()
+            .map(|(_, channel)| 1 + usize::from(channel.is_some()))
+            .sum()
+    }
+
+    /// Whether the endpoint at `address` is currently reported to tonic.
+    pub(crate) fn has_endpoint(&self, address: &SocketAddr) -> bool {
+        self.endpoints
+            .lock()
+            .expect("endpoints lock poisoned")
+            .contains_key(address)
+    }
+
+    /// Wait until the first successful probe has been committed.
+    pub(crate) async fn first_resolution(&self) {
+        let mut resolved = self.resolved.subscribe();
+        // The sender lives as long as `self`, so waiting can not fail.
+        let _ = resolved.wait_for(|resolved| *resolved).await;
+    }
+
+    /// Whether the service definitions are currently being resolved.
+    pub(crate) fn is_probing(&self) -> bool {
+        self.probing.load(Ordering::Relaxed)
+    }
+
+    /// Take a snapshot of the service definitions, the endpoints reported to tonic
+    /// and the outcome of the last probe.
+    pub(crate) fn snapshot(&self) -> DiscoverySnapshot {
+        let mut endpoints: Vec<SocketAddr> = self
+            .endpoints
+            .lock()
+            .expect("endpoints lock poisoned")
+            .keys()
+            .copied()
+            .collect();
+        endpoints.sort_unstable();
+
+        let (last_probe, last_error) = self
+            .last_probe
+            .lock()
+            .expect("last probe lock poisoned")
+            .clone()
+            .map_or((None, None), |(at, error)| (Some(at), error));
+
+        DiscoverySnapshot {
+            service_definitions: self.service_definitions(),
+            endpoints,
+            last_probe,
+            last_error,
+            generation: self.generation.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record the outcome of a probe that has just completed.
+    fn record_probe(&self, result: &Result<(), ProbeError>) {
+        let error = result.as_ref().err().map(|err| {
+            // Render the whole chain of causes, as `{:#}` does for `anyhow::Error`.
+            let mut message = err.to_string();
+            let mut source = std::error::Error::source(err);
+            while let Some(cause) = source {
+                message.push_str(": ");
+                message.push_str(&cause.to_string());
+                source = cause.source();
+            }
+            message
+        });
+
+        *self.last_probe.lock().expect("last probe lock poisoned") =
+            Some((SystemTime::now(), error));
+    }
+
+    /// Keep track of a change that has been reported to tonic.
+    fn track_change(&self, change: &Change<SocketAddr, Endpoint>) {
+        let mut endpoints = self.endpoints.lock().expect("endpoints lock poisoned");
+        match change {
+            Change::Insert(address, endpoint) => {
+                endpoints.insert(*address, (endpoint.clone(), None));
+            }
+            Change::Remove(address) => {
+                endpoints.remove(address);
+            }
+        }
+    }
+}
+
+/// Get the message a panic has been raised with, if it is a string.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>")
+}
+
+/// A random delay between zero and `max`, derived from the seed of the service if any.
+fn random_delay(
+    max: tokio::time::Duration,
+    seed: Option<(u64, &ServiceDefinition)>,
+) -> tokio::time::Duration {
+    use std::collections::hash_map::{DefaultHasher, RandomState};
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    let random = match seed {
+        // The keys of a `DefaultHasher` are fixed, so a seed always gives the same delay,
+        // while the services sharing a seed still get different ones.
+        Some(seed) => {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            hasher.finish()
+        }
+        // Every `RandomState` is seeded differently, which is random enough to spread probes.
+        None => RandomState::new().build_hasher().finish(),
+    };
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+/// Explain why no connection can ever be established to `address`, if that is the case,
+/// e.g. because a misbehaving lookup service returned a port 0.
+fn unconnectable_reason(address: &SocketAddr) -> Option<&'static str> {
+    if address.port() == 0 {
+        Some("port 0 can not be connected to")
+    } else if address.ip().is_unspecified() {
+        Some("the unspecified address can not be connected to")
+    } else if address.ip().is_multicast() {
+        Some("a multicast address can not be connected to")
+    } else {
+        None
+    }
+}
+
+/// Drop from `endpoints` the addresses that can never be connected to, logging why,
+/// so that they are neither reported to tonic nor counted as endpoints.
+fn drop_unconnectable(endpoints: &mut HashSet<SocketAddr>) {
+    endpoints.retain(|address| match unconnectable_reason(address) {
+        Some(reason) => {
+            tracing::warn!("skipping invalid endpoint {}: {}", address, reason);
+            false
+        }
+        None => true,
+    });
+}
+
+/// Suggest a TLS mismatch between the client and the endpoint as the cause of `err`,
+/// if it looks like one, to be appended to the logged error.
+///
+/// A TLS client reading the plaintext response of the endpoint fails the handshake with
+/// invalid data, while a plaintext client reads the TLS alert of the endpoint as an
+/// HTTP/2 frame that is way too large.
+pub(crate) fn tls_mismatch_hint(
+    tls_enabled: bool,
+    err: &(dyn std::error::Error + 'static),
+) -> String {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if tls_enabled {
+            if let Some(err) = err.downcast_ref::<std::io::Error>() {
+                if err.kind() == std::io::ErrorKind::InvalidData {
+                    return " (the TLS handshake failed, is the endpoint serving plaintext?)"
+                        .to_string();
+                }
+            }
+        } else if err
+            .downcast_ref::<h2::Error>()
+            .is_some_and(|err| err.reason() == Some(h2::Reason::FRAME_SIZE_ERROR))
+        {
+            return " (the endpoint sent an invalid HTTP/2 frame, is it expecting TLS?)"
+                .to_string();
+        }
+        source = err.source();
+    }
+
+    String::new()
+}
+
+/// The addresses of the changes reported to tonic, for the [`ProbeObserver`].
+#[derive(Default)]
+struct ReportedAddresses {
+    added: Vec<SocketAddr>,
+    removed: Vec<SocketAddr>,
+}
+
+/// Flags the [`ProbeState`] as probing until dropped, so that the flag is cleared
+/// even if the resolution is cancelled (e.g. by the eager resolution timeout).
+struct ProbingGuard<'a>(&'a ProbeState);
+
+impl<'a> ProbingGuard<'a> {
+    fn start(state: &'a ProbeState) -> Self {
+        state.probing.store(true, Ordering::Relaxed);
+        Self(state)
+    }
+}
+
+impl Drop for ProbingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.probing.store(false, Ordering::Relaxed);
+    }
 }
 
 /// [`GrpcServiceProbe`] looks up IP addresses associated with the configured `host_name`
 /// once every `probe_interval`.
+/// When more than one [`ServiceDefinition`] is registered, the union of their endpoints is reported.
 /// If a new IP address is discovered or an old one disappears it notifies the [`tonic`] gRPC client.
 ///
 /// The tonic load balancing side that is being notified work under the following assumptions:
@@ -31,16 +486,59 @@ pub struct GrpcServiceProbe<Lookup>
 where
     Lookup: LookupService,
 {
-    service_definition: ServiceDefinition,
-    scheme: http::uri::Scheme,
+    state: Arc<ProbeState>,
     dns_lookup: Lookup,
     probe_interval: tokio::time::Duration,
     endpoint_timeout: Option<tokio::time::Duration>,
     endpoint_connect_timeout: Option<tokio::time::Duration>,
+    http2_adaptive_window: Option<bool>,
+    endpoint_layer: Option<EndpointLayerFn>,
+    change_ordering: ChangeOrdering,
+    partial_result_policy: PartialResultPolicy,
+    full_buffer_policy: FullBufferPolicy,
+    tcp_precheck: bool,
+    grpc_precheck: bool,
+    observer: Option<Arc<dyn ProbeObserver>>,
+    snapshot_log_interval: Option<u32>,
+    on_endpoints_available: Option<EndpointsCallback>,
+    on_endpoints_exhausted: Option<EndpointsCallback>,
+    balancing_seed: Option<u64>,
+    change_report_threshold: Option<ChangeReportThreshold>,
+    eject_unconnectable_after: Option<u32>,
+    adaptive_interval: Option<(tokio::time::Duration, tokio::time::Duration)>,
+    endpoint_uri_builder: Option<EndpointUriFn>,
+    initial_probe_jitter: Option<tokio::time::Duration>,
+    max_endpoints_hard_limit: Option<usize>,
+    log_probe_errors: bool,
+    on_consecutive_failures: Option<(u32, FailuresCallback)>,
+    zero_downtime_cutover: bool,
+    max_open_connections: Option<usize>,
+    removal_confirmations: u32,
+    min_healthy_warn: Option<usize>,
+    address_rewrite: Option<AddressRewriteFn>,
+    /// How many probes failed since the last successful one.
+    consecutive_failures: u32,
+    /// Whether the committed endpoints are fewer than `min_healthy_warn`.
+    below_min_healthy: bool,
+    /// How many consecutive probes every committed endpoint has been missing from.
+    missing_probes: HashMap<SocketAddr, u32>,
+    /// The number of successful probes, and the one every recently removed endpoint
+    /// was removed by, for the [`ProbeChurn`] of the [`ProbeObserver`].
+    committed_probes: u64,
+    removed_by_probe: HashMap<SocketAddr, u64>,
+    /// How many consecutive connection checks every resolved endpoint has failed.
+    failed_checks: HashMap<SocketAddr, u32>,
+    /// How long to wait before the next probe, as hinted by the last successful resolution.
+    refresh_after: Option<tokio::time::Duration>,
+    /// The interval between probes, when it adapts to the changes they find.
+    adaptive_current: tokio::time::Duration,
+    /// The number of changes found by the last probe, unless it failed.
+    last_changeset_len: Option<usize>,
+    /// When the probe has been constructed, to time the first resolution.
+    started_at: tokio::time::Instant,
     /// The set of last reported endpoints by `dns_lookup`.
     endpoints: HashSet<SocketAddr>,
     endpoint_reporter: Sender<Change<SocketAddr, Endpoint>>,
-    tls_config: Option<ClientTlsConfig>,
 }
 
 /// Config parameters to customize the behavior of `GrpcServiceProbe`.
@@ -48,8 +546,8 @@ pub struct GrpcServiceProbeConfig<Lookup>
 where
     Lookup: LookupService,
 {
-    /// the host name to resolve dns for and the service port.
-    pub service_definition: ServiceDefinition,
+    /// The state shared with the channel, which holds the service definitions to resolve.
+    pub state: Arc<ProbeState>,
     /// The lookup resolver.
     /// We are using a generic parameter and a trait constraint to allow mocking of DNS resolution in tests.
     pub dns_lookup: Lookup,
@@ -59,9 +557,60 @@ where
     pub endpoint_timeout: Option<tokio::time::Duration>,
     /// A connection timeout that will be applied to every endpoint.
     pub endpoint_connect_timeout: Option<tokio::time::Duration>,
+    /// Whether the endpoints use the adaptive flow control window of HTTP/2.
+    pub http2_adaptive_window: Option<bool>,
+    /// A function customizing every endpoint before it is reported.
+    pub endpoint_layer: Option<EndpointLayerFn>,
+    /// The order in which inserts and removes of a changeset are reported.
+    pub change_ordering: ChangeOrdering,
+    /// How the addresses that can not be turned into an endpoint are handled.
+    pub partial_result_policy: PartialResultPolicy,
+    /// How a changeset is reported when tonic's buffer of changes is full.
+    pub full_buffer_policy: FullBufferPolicy,
+    /// Whether new endpoints have to accept a TCP connection before being reported.
+    pub tcp_precheck: bool,
+    /// Whether new endpoints have to answer a gRPC request before being reported.
+    pub grpc_precheck: bool,
+    /// Notified of the outcome of every probe.
+    pub observer: Option<Arc<dyn ProbeObserver>>,
+    /// Log the full set of endpoints once every this many probes.
+    pub snapshot_log_interval: Option<u32>,
+    /// A callback fired when the endpoints go from none to some.
+    pub on_endpoints_available: Option<EndpointsCallback>,
+    /// A callback fired when the endpoints go from some to none.
+    pub on_endpoints_exhausted: Option<EndpointsCallback>,
+    /// The seed of the initial probe jitter, which is random when unset.
+    pub balancing_seed: Option<u64>,
+    /// Defer the endpoint changes until they add up to this threshold.
+    pub change_report_threshold: Option<ChangeReportThreshold>,
+    /// Eject the endpoints failing this many connection checks in a row, checking the new
+    /// and the failing endpoints with a connection and a request on every probe.
+    pub eject_unconnectable_after: Option<u32>,
+    /// The bounds of the probe interval, shortened while the endpoints churn.
+    pub adaptive_interval: Option<(tokio::time::Duration, tokio::time::Duration)>,
+    /// A function building the uri of every endpoint from its address.
+    pub endpoint_uri_builder: Option<EndpointUriFn>,
+    /// The maximum random delay before the first probe.
+    pub initial_probe_jitter: Option<tokio::time::Duration>,
+    /// Reject the resolutions with more endpoints than this.
+    pub max_endpoints_hard_limit: Option<usize>,
+    /// Whether the failed probes are logged, rather than only recorded.
+    pub log_probe_errors: bool,
+    /// A callback fired every time the probe fails this many times in a row.
+    pub on_consecutive_failures: Option<(u32, FailuresCallback)>,
+    /// Whether a full replacement of the endpoints waits for the new ones to be ready.
+    pub zero_downtime_cutover: bool,
+    /// The maximum number of endpoints reported to tonic, each holding a connection.
+    pub max_open_connections: Option<usize>,
+    /// How many consecutive probes an endpoint has to be missing from before it is removed.
+    pub removal_confirmations: u32,
+    /// Warn when fewer endpoints than this are committed.
+    pub min_healthy_warn: Option<usize>,
+    /// A function rewriting every resolved address before the endpoint is built.
+    pub address_rewrite: Option<AddressRewriteFn>,
 }
 
-impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
+impl<Lookup: LookupService + Sync> GrpcServiceProbe<Lookup> {
     /// Construct `GrpcServiceProbe` with a `GrpcServiceProbeConfig` and
     /// the channel `endpoint_reporter` that will send endpoint changes.
     pub fn new_with_reporter(
@@ -69,24 +618,51 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
         endpoint_reporter: Sender<Change<SocketAddr, Endpoint>>,
     ) -> GrpcServiceProbe<Lookup> {
         Self {
-            service_definition: config.service_definition,
+            state: config.state,
             dns_lookup: config.dns_lookup,
             probe_interval: config.probe_interval,
             endpoint_timeout: config.endpoint_timeout,
             endpoint_connect_timeout: config.endpoint_connect_timeout,
+            http2_adaptive_window: config.http2_adaptive_window,
+            endpoint_layer: config.endpoint_layer,
+            change_ordering: config.change_ordering,
+            partial_result_policy: config.partial_result_policy,
+            full_buffer_policy: config.full_buffer_policy,
+            tcp_precheck: config.tcp_precheck,
+            grpc_precheck: config.grpc_precheck,
+            observer: config.observer,
+            snapshot_log_interval: config.snapshot_log_interval,
+            on_endpoints_available: config.on_endpoints_available,
+            on_endpoints_exhausted: config.on_endpoints_exhausted,
+            balancing_seed: config.balancing_seed,
+            change_report_threshold: config.change_report_threshold,
+            eject_unconnectable_after: config.eject_unconnectable_after,
+            adaptive_interval: config.adaptive_interval,
+            endpoint_uri_builder: config.endpoint_uri_builder,
+            initial_probe_jitter: config.initial_probe_jitter,
+            max_endpoints_hard_limit: config.max_endpoints_hard_limit,
+            log_probe_errors: config.log_probe_errors,
+            on_consecutive_failures: config.on_consecutive_failures,
+            zero_downtime_cutover: config.zero_downtime_cutover,
+            max_open_connections: config.max_open_connections,
+            removal_confirmations: config.removal_confirmations,
+            missing_probes: HashMap::new(),
+            failed_checks: HashMap::new(),
+            committed_probes: 0,
+            removed_by_probe: HashMap::new(),
+            refresh_after: None,
+            adaptive_current: match config.adaptive_interval {
+                Some((min, max)) => config.probe_interval.clamp(min, max),
+                None => config.probe_interval,
+            },
+            last_changeset_len: None,
+            started_at: tokio::time::Instant::now(),
+            min_healthy_warn: config.min_healthy_warn,
+            below_min_healthy: false,
+            consecutive_failures: 0,
+            address_rewrite: config.address_rewrite,
             endpoints: HashSet::new(),
             endpoint_reporter,
-            scheme: http::uri::Scheme::HTTP,
-            tls_config: None,
-        }
-    }
-
-    /// Enable tls for all endpoints.
-    pub fn with_tls(self, tls_config: ClientTlsConfig) -> GrpcServiceProbe<Lookup> {
-        Self {
-            tls_config: Some(tls_config),
-            scheme: http::uri::Scheme::HTTPS,
-            ..self
         }
     }
 
@@ -95,134 +671,818 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
     /// is closed, e.g, the client has been deconstructed.
     /// Any other errors are seen as transient, and therefore retried after `self.probe_interval`.
     pub async fn probe(mut self) -> Result<(), anyhow::Error> {
+        let snapshot_log_interval = self.snapshot_log_interval.filter(|interval| *interval > 0);
+
+        let mut probes_since_snapshot: u32 = 0;
+
+        if let Some(max) = self.initial_probe_jitter {
+            let service_definition = self.state.service_definition();
+            let seed = self.balancing_seed.map(|seed| (seed, service_definition));
+            DefaultRuntime::sleep(random_delay(max, seed)).await;
+        }
+
         loop {
+            let started = tokio::time::Instant::now();
             self.probe_once().await.or_else(|err| {
                 // Only terminate if the changeset channel has been closed.
                 if let ProbeError::ChangesetSenderClosed(_) = err {
                     Err(err)
                 } else {
+                    if self.log_probe_errors {
+                        tracing::warn!("probe failed, the endpoints are left unchanged: {:?}", err);
+                    }
                     Ok(())
                 }
             })?;
 
-            tokio::time::sleep(self.probe_interval).await;
+            if let Some(interval) = snapshot_log_interval {
+                probes_since_snapshot += 1;
+                if probes_since_snapshot == interval {
+                    tracing::info!(endpoints = ?self.endpoints, "endpoint set snapshot");
+                    probes_since_snapshot = 0;
+                }
+            }
+
+            // Follow the hint of the last successful resolution, if any.
+            let interval = match self.refresh_after.take() {
+                Some(refresh_after) => refresh_after,
+                None => self.next_interval(),
+            };
+            tokio::select! {
+                _ = DefaultRuntime::sleep(interval) => {}
+                _ = self.state.reprobe.notified() => {
+                    // Keep a channel whose calls keep failing from hammering the lookup service.
+                    let delay = MIN_REPROBE_DELAY.min(interval);
+                    DefaultRuntime::sleep(delay.saturating_sub(started.elapsed())).await;
+                }
+            }
         }
     }
 
-    /// Update tonic with a set of IPs that are retrieved by querying `hostname`.
+    /// The delay until the next probe: the probe interval, unless it adapts to the changes
+    /// found by the probes, dropping to its minimum when the last probe found any and
+    /// doubling towards its maximum when it did not. A failed probe keeps the interval.
+    fn next_interval(&mut self) -> tokio::time::Duration {
+        let Some((min, max)) = self.adaptive_interval else {
+            return self.probe_interval;
+        };
+
+        self.adaptive_current = match self.last_changeset_len {
+            Some(0) => (self.adaptive_current * 2).clamp(min, max),
+            Some(_) => min,
+            None => self.adaptive_current,
+        };
+        self.adaptive_current
+    }
+
+    /// Update tonic with a set of IPs that are retrieved by querying the `hostname`
+    /// of every registered [`ServiceDefinition`].
     pub async fn probe_once(&mut self) -> Result<(), ProbeError> {
-        match self
-            .dns_lookup
-            .resolve_service_endpoints(&self.service_definition)
-            .await
+        let result = self.resolve_and_report().await;
+        self.state.record_probe(&result);
+        self.track_failures(&result);
+
+        result
+    }
+
+    /// Count the consecutive failed probes, and fire the callback every time
+    /// the configured number of them is reached.
+    fn track_failures(&mut self, result: &Result<(), ProbeError>) {
+        match result {
+            Ok(()) => self.consecutive_failures = 0,
+            // The channel is gone, there is nothing left to remediate.
+            Err(ProbeError::ChangesetSenderClosed(_)) => {}
+            Err(_) => {
+                self.consecutive_failures += 1;
+                if let Some((failures, callback)) = &self.on_consecutive_failures {
+                    if self.consecutive_failures.is_multiple_of(*failures) {
+                        tracing::warn!(
+                            "the probe failed {} times in a row",
+                            self.consecutive_failures
+                        );
+                        callback();
+                    }
+                }
+            }
+        }
+    }
+
+    async fn resolve_and_report(&mut self) -> Result<(), ProbeError> {
+        self.last_changeset_len = None;
+        let Resolution {
+            mut endpoints,
+            refresh_after,
+            valid_until,
+        } = {
+            let _probing = ProbingGuard::start(&self.state);
+            // Abort a slow resolution as soon as the client is dropped,
+            // rather than keeping the probe task alive until it completes.
+            let start = tokio::time::Instant::now();
+            let endpoints = tokio::select! {
+                endpoints = self.resolve_endpoints() => endpoints,
+                _ = self.endpoint_reporter.closed() => {
+                    return Err(ProbeError::ChangesetSenderClosed(anyhow::anyhow!("The channel has been closed while resolving endpoints, this is probably due to the gRPC client being dropped.")));
+                }
+            };
+
+            let duration = start.elapsed();
+            tracing::debug!(
+                ?duration,
+                succeeded = endpoints.is_ok(),
+                "resolved endpoints"
+            );
+            if let Some(observer) = &self.observer {
+                observer.on_resolution(duration, endpoints.is_ok());
+            }
+
+            endpoints?
+        };
+        if let Some(limit) = self.max_endpoints_hard_limit {
+            // Most likely a bug of the lookup service: keep the endpoints we have.
+            if endpoints.len() > limit {
+                return Err(ProbeError::TooManyEndpoints {
+                    resolved: endpoints.len(),
+                    limit,
+                });
+            }
+        }
+        self.refresh_after = refresh_after;
+        drop_unconnectable(&mut endpoints);
+        if let Some(valid_until) = valid_until {
+            tracing::debug!(
+                "the resolved endpoints are valid for {:?}",
+                valid_until.saturating_duration_since(std::time::Instant::now())
+            );
+        }
+        if self.tcp_precheck {
+            self.retain_connectable(&mut endpoints).await;
+        }
+        if self.grpc_precheck {
+            self.retain_grpc_speaking(&mut endpoints).await;
+        }
+        if let Some(checks) = self.eject_unconnectable_after {
+            self.eject_unconnectable(&mut endpoints, checks).await;
+        }
+        if self.removal_confirmations > 1 {
+            self.defer_removals(&mut endpoints);
+        }
+        if let Some(max_open_connections) = self.max_open_connections {
+            self.cap_new_endpoints(&mut endpoints, max_open_connections);
+        }
+        if let Some(threshold) = self.change_report_threshold {
+            self.defer_small_changes(&mut endpoints, threshold);
+        }
+        // Staging a cutover reports changes already: the churn of the probe
+        // is observed from the endpoints committed before any of them.
+        let before = self.observer.is_some().then(|| self.endpoints.clone());
+        if self.zero_downtime_cutover
+            && self.is_full_replacement(&endpoints)
+            && !self.stage_cutover(&endpoints).await?
         {
-            Ok(endpoints) => {
-                let changeset = self.create_changeset(&endpoints).await;
+            endpoints = self.endpoints.clone();
+        }
+        let changeset = self.create_changeset(&endpoints).await?;
+        self.last_changeset_len = Some(changeset.len());
+
+        // Report the changeset to `tonic` and commit the new endpoints
+        // if we succeed to report the changeset.
+        self.report_and_commit(changeset, before).await.map_err(|e| {
+                tracing::error!("Failed to report the discovered DNS changeset. The gRPC client has closed the channel therefore the DNS probe loop will exit.\n{:?}", e);
+                e
+            })?;
+
+        Ok(())
+    }
+
+    /// Report `endpoints` to tonic and commit them without resolving anything,
+    /// so that the next probe reconciles the resolved endpoints against them.
+    ///
+    /// Seeding does not count as a successful resolution.
+    pub async fn seed(&mut self, mut endpoints: HashSet<SocketAddr>) -> Result<(), ProbeError> {
+        drop_unconnectable(&mut endpoints);
+        let changeset = self.create_changeset(&endpoints).await?;
+        self.report(changeset).await?;
+
+        Ok(())
+    }
+
+    /// Resolve the union of the endpoints of all the registered service definitions.
+    ///
+    /// The definitions are resolved concurrently, so a probe takes as long as the slowest one.
+    /// If any of the definitions fails to resolve the whole probe fails, so that a
+    /// transient error for one service never removes its endpoints from the set.
+    /// The resolved addresses are rewritten here, so that everything downstream,
+    /// tonic's balancer included, is keyed by the rewritten addresses.
+    /// The shortest of the refresh hints, and the earliest expiry, of the definitions are kept.
+    async fn resolve_endpoints(&self) -> Result<Resolution, ProbeError> {
+        let service_definitions = self.state.service_definitions();
+
+        let resolved = futures::future::try_join_all(service_definitions.iter().map(
+            |service_definition| async move {
+                // A panicking lookup service would kill the probe task and freeze the
+                // endpoints forever: treat the panic as a failed resolution instead.
+                let resolution = AssertUnwindSafe(
+                    self.dns_lookup
+                        .resolve_service_endpoints_with_metadata(service_definition),
+                )
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|panic| {
+                    let message = panic_message(panic.as_ref());
+                    // Unlike a failed resolution, a panic is a bug and is always logged.
+                    tracing::error!(
+                        hostname = service_definition.hostname(),
+                        port = service_definition.port(),
+                        "the lookup service panicked: {}",
+                        message
+                    );
+                    Err(anyhow::anyhow!("the lookup service panicked: {}", message))
+                });
+
+                resolution.map_err(|err| ProbeError::ResolveServiceDefinition {
+                    hostname: service_definition.hostname().to_string(),
+                    port: service_definition.port(),
+                    source: err.context("failed to resolve ips from host"),
+                })
+            },
+        ))
+        .await?;
+
+        let refresh_after = resolved
+            .iter()
+            .filter_map(|resolution| resolution.refresh_after)
+            .min();
+        let valid_until = resolved
+            .iter()
+            .filter_map(|resolution| resolution.valid_until)
+            .min();
+        let endpoints = resolved
+            .into_iter()
+            .flat_map(|resolution| resolution.endpoints);
+        let endpoints = match &self.address_rewrite {
+            Some(rewrite) => endpoints.map(rewrite).collect(),
+            None => endpoints.collect(),
+        };
+
+        Ok(Resolution {
+            endpoints,
+            refresh_after,
+            valid_until,
+        })
+    }
+
+    /// Drop the new endpoints in `endpoints` that do not accept a TCP connection.
+    ///
+    /// The dropped endpoints are not committed, so they are checked again on the next probe.
+    async fn retain_connectable(&self, endpoints: &mut HashSet<SocketAddr>) {
+        let timeout = self
+            .endpoint_connect_timeout
+            .unwrap_or(DEFAULT_PRECHECK_TIMEOUT);
+
+        let unreachable = futures::future::join_all(endpoints.difference(&self.endpoints).map(
+            |address| async move {
+                match tokio::time::timeout(timeout, TcpStream::connect(address)).await {
+                    Ok(Ok(_)) => None,
+                    Ok(Err(err)) => {
+                        tracing::warn!("tcp precheck failed for {}: {:?}", address, err);
+                        Some(*address)
+                    }
+                    Err(_) => {
+                        tracing::warn!("tcp precheck timed out for {}", address);
+                        Some(*address)
+                    }
+                }
+            },
+        ))
+        .await;
+
+        for address in unreachable.into_iter().flatten() {
+            endpoints.remove(&address);
+        }
+    }
+
+    /// Drop the new endpoints in `endpoints` that do not answer a gRPC request
+    /// with a gRPC response, e.g. because they point at a plain HTTP port.
+    ///
+    /// The dropped endpoints are not committed, so they are checked again on the next probe.
+    async fn retain_grpc_speaking(&self, endpoints: &mut HashSet<SocketAddr>) {
+        let timeout = self
+            .endpoint_connect_timeout
+            .unwrap_or(DEFAULT_PRECHECK_TIMEOUT);
 
-                // Report the changeset to `tonic` and commit the new endpoints
-                // if we succeed to report the changeset.
-                self.report_and_commit(changeset, endpoints).await.map_err(|e| {
-                        tracing::error!("Failed to report the discovered DNS changeset. The gRPC client has closed the channel therefore the DNS probe loop will exit.\n{:?}", e);
-                        e
-                    })?;
+        let misconfigured = futures::future::join_all(endpoints.difference(&self.endpoints).map(
+            |address| async move {
+                match tokio::time::timeout(timeout, self.check_grpc(address)).await {
+                    Ok(Ok(())) => None,
+                    Ok(Err(err)) => {
+                        let hint = tls_mismatch_hint(self.state.is_tls_enabled(), err.as_ref());
+                        tracing::warn!("grpc precheck failed for {}, the endpoint does not seem to serve gRPC on this port{}: {:?}", address, hint, err);
+                        Some(*address)
+                    }
+                    Err(_) => {
+                        tracing::warn!("grpc precheck timed out for {}", address);
+                        Some(*address)
+                    }
+                }
+            },
+        ))
+        .await;
+
+        for address in misconfigured.into_iter().flatten() {
+            endpoints.remove(&address);
+        }
+    }
+
+    /// Drop from `endpoints` the ones that failed the last `checks` connection checks in a row,
+    /// i.e. whose connection or gRPC precheck request failed, e.g. because of a TLS mismatch.
+    ///
+    /// Only the new endpoints and the ones already failing are checked, each with a new
+    /// connection and a gRPC request: the committed endpoints that passed their last check
+    /// are left alone. An ejected endpoint is checked on every probe, and comes back as soon
+    /// as it passes a check again. The new endpoints that passed the gRPC precheck of this
+    /// probe are not checked twice.
+    async fn eject_unconnectable(&mut self, endpoints: &mut HashSet<SocketAddr>, checks: u32) {
+        let timeout = self
+            .endpoint_connect_timeout
+            .unwrap_or(DEFAULT_PRECHECK_TIMEOUT);
+
+        self.failed_checks
+            .retain(|address, _| endpoints.contains(address));
+        let (prechecked, unchecked): (Vec<SocketAddr>, Vec<SocketAddr>) = endpoints
+            .iter()
+            .copied()
+            .filter(|address| {
+                !self.endpoints.contains(address) || self.failed_checks.contains_key(address)
+            })
+            .partition(|address| self.grpc_precheck && !self.endpoints.contains(address));
+
+        let probe = &*self;
+        let mut results =
+            futures::future::join_all(unchecked.into_iter().map(|address| async move {
+                let result = tokio::time::timeout(timeout, probe.check_grpc(&address))
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out")));
+                (address, result)
+            }))
+            .await;
+        results.extend(prechecked.into_iter().map(|address| (address, Ok(()))));
+
+        for (address, result) in results {
+            match result {
+                Ok(()) => {
+                    if self.failed_checks.remove(&address).unwrap_or(0) >= checks {
+                        tracing::info!("{} passed a connection check again, restoring it", address);
+                    }
+                }
+                Err(err) => {
+                    let failed = self.failed_checks.entry(address).or_insert(0);
+                    *failed += 1;
+                    if *failed == checks {
+                        let hint = tls_mismatch_hint(self.state.is_tls_enabled(), err.as_ref());
+                        tracing::warn!(
+                            "ejecting {} after {} failed connection checks in a row{}: {:?}",
+                            address,
+                            checks,
+                            hint,
+                            err
+                        );
+                    }
+                }
+            }
+        }
+
+        endpoints.retain(|address| {
+            self.failed_checks
+                .get(address)
+                .is_none_or(|failed| *failed < checks)
+        });
+    }
+
+    /// Replace `endpoints` with the committed ones while they differ by less than `threshold`.
+    fn defer_small_changes(
+        &self,
+        endpoints: &mut HashSet<SocketAddr>,
+        threshold: ChangeReportThreshold,
+    ) {
+        if self.endpoints.is_empty() || endpoints.is_empty() {
+            return;
+        }
+
+        let difference = self.endpoints.symmetric_difference(endpoints).count();
+        if difference > 0 && !threshold.is_reached(difference, self.endpoints.len()) {
+            tracing::debug!(
+                difference,
+                "deferring endpoint changes below the report threshold"
+            );
+            endpoints.clone_from(&self.endpoints);
+        }
+    }
+
+    /// Keep in `endpoints` the committed endpoints that have been missing from fewer than
+    /// `removal_confirmations` consecutive probes, so that they are not removed yet.
+    fn defer_removals(&mut self, endpoints: &mut HashSet<SocketAddr>) {
+        // Forget the endpoints that have been resolved again, or removed.
+        self.missing_probes
+            .retain(|address, _| !endpoints.contains(address) && self.endpoints.contains(address));
+
+        for address in self
+            .endpoints
+            .difference(endpoints)
+            .copied()
+            .collect::<Vec<_>>()
+        {
+            let missing = self.missing_probes.entry(address).or_insert(0);
+            *missing += 1;
+            if *missing < self.removal_confirmations {
+                tracing::debug!(
+                    "{} has been missing from {} probes, keeping it until it is missing from {}",
+                    address,
+                    missing,
+                    self.removal_confirmations
+                );
+                endpoints.insert(address);
             }
-            Err(err) => {
-                return Err(ProbeError::ResolveServiceDefinition(
-                    err.context("failed to resolve ips from host"),
-                ));
+        }
+    }
+
+    /// Drop new endpoints from `endpoints` so that it holds at most `max_endpoints`,
+    /// keeping all the committed ones.
+    ///
+    /// The dropped endpoints are not committed, so they are added by a later probe
+    /// once enough endpoints have been removed.
+    fn cap_new_endpoints(&self, endpoints: &mut HashSet<SocketAddr>, max_endpoints: usize) {
+        let mut new_endpoints: Vec<_> = endpoints.difference(&self.endpoints).copied().collect();
+        let committed = endpoints.len() - new_endpoints.len();
+        let allowed = max_endpoints.saturating_sub(committed);
+        if new_endpoints.len() <= allowed {
+            return;
+        }
+
+        // Always pick the same endpoints, rather than a different subset on every probe.
+        new_endpoints.sort_unstable();
+        tracing::warn!(
+            "reached the maximum of {} open connections, {} new endpoints are left out",
+            max_endpoints,
+            new_endpoints.len() - allowed
+        );
+        for address in &new_endpoints[allowed..] {
+            endpoints.remove(address);
+        }
+    }
+
+    /// Whether `endpoints` replaces every committed endpoint, e.g. during a blue-green cutover.
+    fn is_full_replacement(&self, endpoints: &HashSet<SocketAddr>) -> bool {
+        !self.endpoints.is_empty() && !endpoints.is_empty() && self.endpoints.is_disjoint(endpoints)
+    }
+
+    /// Report the new endpoints of a full replacement alongside the committed ones, and
+    /// return whether at least one of them accepts a connection, so that the committed
+    /// ones can be removed.
+    ///
+    /// The new endpoints are withdrawn if none of them is ready: the committed ones keep
+    /// serving, and the cutover is attempted again on the next probe.
+    async fn stage_cutover(&mut self, endpoints: &HashSet<SocketAddr>) -> Result<bool, ProbeError> {
+        let committed = self.endpoints.clone();
+        let staged = committed.union(endpoints).copied().collect();
+        let changeset = self.create_changeset(&staged).await?;
+        if !self.report(changeset).await? {
+            tracing::warn!(
+                "failed to report the new endpoints of a cutover, keeping the current ones"
+            );
+        } else if self.any_ready(self.endpoints.difference(&committed)).await {
+            return Ok(true);
+        } else {
+            tracing::warn!(
+                "none of the {} new endpoints accepts a connection, keeping the {} current ones",
+                endpoints.len(),
+                committed.len()
+            );
+        }
+
+        let changeset = self.create_changeset(&committed).await?;
+        self.report(changeset).await?;
+
+        Ok(false)
+    }
+
+    /// Whether any of `addresses` accepts a connection, bounded by the connect timeout.
+    async fn any_ready(&self, addresses: impl Iterator<Item = &SocketAddr>) -> bool {
+        let timeout = self
+            .endpoint_connect_timeout
+            .unwrap_or(DEFAULT_PRECHECK_TIMEOUT);
+
+        let ready = futures::future::join_all(addresses.map(|address| async move {
+            let endpoint = self.build_endpoint(address).ok()?;
+            match tokio::time::timeout(timeout, endpoint.connect()).await {
+                Ok(Ok(_)) => Some(()),
+                Ok(Err(err)) => {
+                    let hint = tls_mismatch_hint(self.state.is_tls_enabled(), &err);
+                    tracing::debug!(
+                        "cutover endpoint {} is not ready{}: {:?}",
+                        address,
+                        hint,
+                        err
+                    );
+                    None
+                }
+                Err(_) => {
+                    tracing::debug!("cutover endpoint {} timed out", address);
+                    None
+                }
             }
+        }))
+        .await;
+
+        ready.into_iter().any(|ready| ready.is_some())
+    }
+
+    /// Send a health check request to `address` and check that the response is a gRPC one.
+    ///
+    /// Any gRPC status, including `UNIMPLEMENTED` for servers without a health service,
+    /// proves that the endpoint speaks gRPC.
+    async fn check_grpc(&self, address: &SocketAddr) -> Result<(), anyhow::Error> {
+        let mut channel = self
+            .build_endpoint(address)?
+            .connect()
+            .await
+            .context("failed to connect")?;
+
+        let request = http::Request::post(GRPC_PRECHECK_PATH)
+            .header(http::header::CONTENT_TYPE, "application/grpc")
+            .header(http::header::TE, "trailers")
+            .body(tonic::body::empty_body())?;
+        let response = tower::ServiceExt::oneshot(&mut channel, request)
+            .await
+            .context("failed to send a gRPC request")?;
+
+        let content_type = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .unwrap_or_default();
+        if !content_type.starts_with("application/grpc") {
+            anyhow::bail!(
+                "the response has status {} and content-type '{}' instead of 'application/grpc'",
+                response.status(),
+                content_type
+            );
         }
 
         Ok(())
     }
 
     /// Construct a changeset and report the endpoint changes to tonic.
+    ///
+    /// New addresses that can not be turned into an [`Endpoint`] are handled
+    /// according to the [`PartialResultPolicy`].
     async fn create_changeset(
         &mut self,
         endpoints: &HashSet<SocketAddr>,
-    ) -> Vec<Change<SocketAddr, Endpoint>> {
-        let mut changeset = Vec::new();
-
-        let remove_set: HashSet<SocketAddr> =
-            self.endpoints.difference(endpoints).copied().collect();
-
-        let add_set: HashSet<SocketAddr> = endpoints.difference(&self.endpoints).copied().collect();
-
-        changeset.extend(
-            add_set
-                .into_iter()
-                .filter_map(|addr| self.build_endpoint(&addr).map(|endpoint| (addr, endpoint)))
-                .map(|(addr, endpoint)| Change::Insert(addr, endpoint)),
-        );
+    ) -> Result<Vec<Change<SocketAddr, Endpoint>>, ProbeError> {
+        // Walk the differences in place rather than collecting them into new sets:
+        // on a stable resolution of a large set both are empty and nothing is allocated.
+        let mut inserts = Vec::new();
+        for &addr in endpoints.difference(&self.endpoints) {
+            match self.build_endpoint(&addr) {
+                Ok(endpoint) => inserts.push(Change::Insert(addr, endpoint)),
+                Err(err) => match self.partial_result_policy {
+                    PartialResultPolicy::Ignore => {
+                        tracing::warn!("skipping endpoint {}: {:?}", addr, err);
+                    }
+                    PartialResultPolicy::FailProbe => {
+                        return Err(ProbeError::BuildEndpoint(
+                            err.context(format!("failed to build endpoint for {}", addr)),
+                        ));
+                    }
+                },
+            }
+        }
+        let removes: Vec<_> = self
+            .endpoints
+            .difference(endpoints)
+            .map(|&addr| Change::Remove(addr))
+            .collect();
 
-        changeset.extend(remove_set.into_iter().map(Change::Remove));
+        let mut changeset = Vec::with_capacity(inserts.len() + removes.len());
+        match self.change_ordering {
+            ChangeOrdering::InsertsFirst => {
+                changeset.extend(inserts);
+                changeset.extend(removes);
+            }
+            ChangeOrdering::RemovesFirst => {
+                changeset.extend(removes);
+                changeset.extend(inserts);
+            }
+            ChangeOrdering::Interleaved => {
+                let (mut inserts, mut removes) = (inserts.into_iter(), removes.into_iter());
+                loop {
+                    match (inserts.next(), removes.next()) {
+                        (None, None) => break,
+                        (insert, remove) => changeset.extend(insert.into_iter().chain(remove)),
+                    }
+                }
+            }
+        }
 
-        changeset
+        Ok(changeset)
     }
 
-    /// Update the endpoint working set to be equal to the result of the last probe.
-    fn overwrite_endpoints(&mut self, current_ips: HashSet<SocketAddr>) {
-        self.endpoints = current_ips;
+    /// The set of endpoints committed by the last successful probe.
+    pub(crate) fn endpoints(&self) -> &HashSet<SocketAddr> {
+        &self.endpoints
     }
 
-    /// Report `changeset` to the gRPC client and commit the changes
-    /// by setting the new working set to the most recent list of endpoints.
+    /// Report `changeset` to the gRPC client and commit the changes to the endpoint working set.
+    ///
+    /// Only the reported changes are committed: the addresses left out of the changeset
+    /// (e.g. because they could not be turned into an [`Endpoint`]) or left to the next
+    /// probe by the [`FullBufferPolicy`] are diffed again by the next probe.
+    ///
+    /// The churn of the probe is observed from `before`, the endpoints committed before
+    /// the probe reported any change, if the [`ProbeObserver`] is set.
     ///
     /// Function fails if the `Sender` is closed.
     #[tracing::instrument(
-        skip(endpoints, self),
+        skip(self, before),
         level = "debug",
         name = "report-and-commit-endpoint-changeset"
     )]
     async fn report_and_commit(
         &mut self,
         changeset: Vec<Change<SocketAddr, Endpoint>>,
-        endpoints: HashSet<SocketAddr>,
+        before: Option<HashSet<SocketAddr>>,
     ) -> Result<(), ProbeError> {
-        for change in changeset {
-            if self.endpoint_reporter.send(change).await.is_err() {
-                return Err(ProbeError::ChangesetSenderClosed(anyhow::anyhow!("Tried to report endpoint changes on a closed channel, this is probably due to the gRPC client being dropped.")));
+        let was_empty = self.endpoints.is_empty();
+
+        // Bail out if the client died, there is nothing left to commit.
+        // Otherwise every change sent to the client has been applied to the endpoints.
+        self.report(changeset).await?;
+        if let Some(before) = before {
+            self.observe_churn(&before);
+        }
+
+        let callback = match (was_empty, self.endpoints.is_empty()) {
+            (true, false) => self.on_endpoints_available.as_ref(),
+            (false, true) => self.on_endpoints_exhausted.as_ref(),
+            _ => None,
+        };
+        if let Some(callback) = callback {
+            callback();
+        }
+
+        if let Some(minimum) = self.min_healthy_warn {
+            let endpoints = self.endpoints.len();
+            let below_min_healthy = endpoints < minimum;
+            if below_min_healthy && !self.below_min_healthy {
+                tracing::warn!(
+                    endpoints,
+                    minimum,
+                    "the number of endpoints dropped below the minimum"
+                );
+                if let Some(observer) = &self.observer {
+                    observer.on_endpoints_below_minimum(endpoints, minimum);
+                }
             }
+            self.below_min_healthy = below_min_healthy;
         }
 
-        // When we reach this point we have sent all the changes to the client
-        // and can overwrite the endpoints.
-        // If we failed earlier the client died so we're in the clear!
-        self.overwrite_endpoints(endpoints);
+        let first_resolution = self
+            .state
+            .resolved
+            .send_if_modified(|resolved| !std::mem::replace(resolved, true));
+        if first_resolution {
+            let elapsed = self.started_at.elapsed();
+            let endpoints = self.endpoints.len();
+            tracing::info!(
+                ?elapsed,
+                endpoints,
+                "resolved the endpoints for the first time"
+            );
+            if let Some(observer) = &self.observer {
+                observer.on_first_resolution(elapsed, endpoints);
+            }
+        }
 
         Ok(())
     }
 
-    fn build_endpoint(&self, ip_address: &SocketAddr) -> Option<Endpoint> {
-        let uri = match ip_address.is_ipv6() {
-            false => format!(
-                "{}://{}:{}",
-                self.scheme,
-                ip_address.ip(),
-                ip_address.port()
-            ),
-            true => format!(
-                "{}://[{}]:{}",
-                self.scheme,
-                ip_address.ip(),
-                ip_address.port()
-            ),
+    /// Notify the [`ProbeObserver`] of how the endpoints changed from `before`.
+    fn observe_churn(&mut self, before: &HashSet<SocketAddr>) {
+        self.committed_probes += 1;
+        let probe = self.committed_probes;
+
+        let stable = before.intersection(&self.endpoints).count();
+        let added: Vec<_> = self.endpoints.difference(before).collect();
+        let readded = added
+            .iter()
+            .filter(|address| self.removed_by_probe.remove(**address).is_some())
+            .count();
+        let removed = before.len() - stable;
+        for address in before.difference(&self.endpoints) {
+            self.removed_by_probe.insert(*address, probe);
+        }
+        self.removed_by_probe
+            .retain(|_, removed_by| probe - *removed_by < CHURN_MEMORY_PROBES);
+
+        if let Some(observer) = &self.observer {
+            observer.on_probe_churn(ProbeChurn {
+                stable,
+                added: added.len(),
+                removed,
+                readded,
+            });
+        }
+    }
+
+    /// Report `changeset` to the gRPC client, returning whether all of it has been reported.
+    ///
+    /// Every reported change is applied to the endpoint working set, so that a changeset
+    /// cut short by the [`FullBufferPolicy`] leaves it matching what tonic has been told.
+    ///
+    /// Function fails if the `Sender` is closed.
+    async fn report(
+        &mut self,
+        changeset: Vec<Change<SocketAddr, Endpoint>>,
+    ) -> Result<bool, ProbeError> {
+        // Only keep track of the reported addresses if anyone is interested in them.
+        let mut reported = self.observer.is_some().then(ReportedAddresses::default);
+        let result = self.send_changeset(changeset, reported.as_mut()).await;
+
+        if let (Some(observer), Some(mut reported)) = (&self.observer, reported) {
+            if !reported.added.is_empty() || !reported.removed.is_empty() {
+                reported.added.sort_unstable();
+                reported.removed.sort_unstable();
+                observer.on_endpoints_changed(&reported.added, &reported.removed);
+            }
+        }
+
+        result
+    }
+
+    /// Send `changeset` to the gRPC client as [`Self::report`] does,
+    /// keeping track of the reported addresses in `addresses`.
+    async fn send_changeset(
+        &mut self,
+        changeset: Vec<Change<SocketAddr, Endpoint>>,
+        mut addresses: Option<&mut ReportedAddresses>,
+    ) -> Result<bool, ProbeError> {
+        if !changeset.is_empty() {
+            self.state.generation.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let closed = || {
+            ProbeError::ChangesetSenderClosed(anyhow::anyhow!("Tried to report endpoint changes on a closed channel, this is probably due to the gRPC client being dropped."))
         };
 
-        let mut endpoint = Endpoint::from_shared(uri)
-            .map_err(|err| {
-                tracing::warn!("endpoint creation error: {:?}", err);
-            })
-            .ok()?;
-
-        if let Some(ref tls_config) = self.tls_config {
-            endpoint = endpoint
-                .tls_config(tls_config.clone())
-                .map_err(|err| {
-                    tracing::warn!("tls error: {:?}", err);
-                    err
-                })
-                .ok()?;
+        let total = changeset.len();
+        for (reported, change) in changeset.into_iter().enumerate() {
+            let permit = match self.full_buffer_policy {
+                FullBufferPolicy::Block => self
+                    .endpoint_reporter
+                    .reserve()
+                    .await
+                    .map_err(|_| closed())?,
+                FullBufferPolicy::SkipChangeset => match self.endpoint_reporter.try_reserve() {
+                    Ok(permit) => permit,
+                    Err(TrySendError::Full(())) => {
+                        tracing::warn!(
+                            "the buffer of endpoint changes is full, {} of {} changes are left to the next probe",
+                            total - reported,
+                            total
+                        );
+                        return Ok(false);
+                    }
+                    Err(TrySendError::Closed(())) => return Err(closed()),
+                },
+            };
+
+            self.state.track_change(&change);
+            match &change {
+                Change::Insert(address, _) => self.endpoints.insert(*address),
+                Change::Remove(address) => self.endpoints.remove(address),
+            };
+            if let Some(addresses) = addresses.as_mut() {
+                match &change {
+                    Change::Insert(address, _) => addresses.added.push(*address),
+                    Change::Remove(address) => addresses.removed.push(*address),
+                }
+            }
+            permit.send(change);
+        }
+
+        Ok(true)
+    }
+
+    fn build_endpoint(&self, ip_address: &SocketAddr) -> Result<Endpoint, anyhow::Error> {
+        let tls_config = self.state.tls_config();
+
+        let mut endpoint = match &self.endpoint_uri_builder {
+            Some(endpoint_uri_builder) => Endpoint::from(endpoint_uri_builder(ip_address)),
+            None => Self::default_endpoint(ip_address, tls_config.is_some())?,
+        };
+
+        if let Some(tls_config) = tls_config {
+            endpoint = endpoint.tls_config(tls_config).context("tls error")?;
         }
 
         if let Some(ref timeout) = self.endpoint_timeout {
@@ -231,7 +1491,595 @@ impl<Lookup: LookupService> GrpcServiceProbe<Lookup> {
         if let Some(ref connect_timeout) = self.endpoint_connect_timeout {
             endpoint = endpoint.connect_timeout(*connect_timeout)
         }
+        if let Some(enabled) = self.http2_adaptive_window {
+            endpoint = endpoint.http2_adaptive_window(enabled);
+        }
+        if let Some(endpoint_layer) = &self.endpoint_layer {
+            endpoint = endpoint_layer(*ip_address, endpoint).context("endpoint layer error")?;
+        }
+
+        Ok(endpoint)
+    }
+
+    /// Build the endpoint of `ip_address`, over https if `tls` is enabled.
+    fn default_endpoint(ip_address: &SocketAddr, tls: bool) -> Result<Endpoint, anyhow::Error> {
+        let scheme = if tls {
+            http::uri::Scheme::HTTPS
+        } else {
+            http::uri::Scheme::HTTP
+        };
+
+        let uri = match ip_address {
+            SocketAddr::V4(address) => format!("{}://{}:{}", scheme, address.ip(), address.port()),
+            // The zone of a link-local address is kept as a numeric scope id, e.g. `fe80::1%2`.
+            // This is not the `%25` of RFC 6874: the connector fails to parse the host as an
+            // IP and resolves it with `getaddrinfo`, on its blocking pool, which returns the
+            // address with its scope id. tonic offers no way to connect to the address itself.
+            SocketAddr::V6(address) if address.scope_id() != 0 => format!(
+                "{}://[{}%{}]:{}",
+                scheme,
+                address.ip(),
+                address.scope_id(),
+                address.port()
+            ),
+            SocketAddr::V6(address) => {
+                format!("{}://[{}]:{}", scheme, address.ip(), address.port())
+            }
+        };
+
+        Endpoint::from_shared(uri).context("endpoint creation error")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Resolves every service definition to the same set of endpoints, empty by default.
+    #[derive(Default)]
+    struct StaticLookup(Mutex<HashSet<SocketAddr>>);
+
+    #[async_trait::async_trait]
+    impl LookupService for StaticLookup {
+        async fn resolve_service_endpoints(
+            &self,
+            _definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    fn probe(change_ordering: ChangeOrdering) -> GrpcServiceProbe<StaticLookup> {
+        let (sender, _) = tokio::sync::mpsc::channel(16);
+        let config = GrpcServiceProbeConfig {
+            state: Arc::new(ProbeState::new(
+                ServiceDefinition::from_parts("test", 5000).unwrap(),
+            )),
+            dns_lookup: StaticLookup::default(),
+            probe_interval: tokio::time::Duration::from_secs(10),
+            endpoint_timeout: None,
+            endpoint_connect_timeout: None,
+            http2_adaptive_window: None,
+            endpoint_layer: None,
+            change_ordering,
+            partial_result_policy: PartialResultPolicy::default(),
+            full_buffer_policy: FullBufferPolicy::default(),
+            tcp_precheck: false,
+            grpc_precheck: false,
+            observer: None,
+            snapshot_log_interval: None,
+            on_endpoints_available: None,
+            on_endpoints_exhausted: None,
+            balancing_seed: None,
+            change_report_threshold: None,
+            eject_unconnectable_after: None,
+            adaptive_interval: None,
+            endpoint_uri_builder: None,
+            initial_probe_jitter: None,
+            max_endpoints_hard_limit: None,
+            log_probe_errors: true,
+            on_consecutive_failures: None,
+            zero_downtime_cutover: false,
+            max_open_connections: None,
+            removal_confirmations: 1,
+            min_healthy_warn: None,
+            address_rewrite: None,
+        };
+
+        GrpcServiceProbe::new_with_reporter(config, sender)
+    }
+
+    fn addresses(ports: &[u16]) -> HashSet<SocketAddr> {
+        ports
+            .iter()
+            .map(|port| SocketAddr::from(([127, 0, 0, 1], *port)))
+            .collect()
+    }
+
+    /// Summarise a changeset as `true` for inserts and `false` for removes.
+    fn kinds(changeset: &[Change<SocketAddr, Endpoint>]) -> Vec<bool> {
+        changeset
+            .iter()
+            .map(|change| matches!(change, Change::Insert(..)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn changeset_follows_change_ordering() {
+        for (change_ordering, expected) in [
+            (ChangeOrdering::InsertsFirst, [true, true, false, false]),
+            (ChangeOrdering::RemovesFirst, [false, false, true, true]),
+            (ChangeOrdering::Interleaved, [true, false, true, false]),
+        ] {
+            let mut probe = probe(change_ordering);
+            probe.endpoints = addresses(&[1, 2]);
+
+            let changeset = probe.create_changeset(&addresses(&[3, 4])).await.unwrap();
+
+            assert_eq!(
+                expected.to_vec(),
+                kinds(&changeset),
+                "{:?}",
+                change_ordering
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn full_buffer_leaves_the_remaining_changes_to_the_next_probe() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(2);
+        let mut probe = probe(ChangeOrdering::default());
+        probe.endpoint_reporter = sender;
+        probe.full_buffer_policy = FullBufferPolicy::SkipChangeset;
+
+        let endpoints = addresses(&[1, 2, 3]);
+        let changeset = probe.create_changeset(&endpoints).await.unwrap();
+        probe.report_and_commit(changeset, None).await.unwrap();
+        assert_eq!(2, probe.endpoints().len());
+
+        // Make room for the remaining change, as tonic would once the channel is called.
+        receiver.recv().await.unwrap();
+        let changeset = probe.create_changeset(&endpoints).await.unwrap();
+        assert_eq!(vec![true], kinds(&changeset));
+        probe.report_and_commit(changeset, None).await.unwrap();
+        assert_eq!(&endpoints, probe.endpoints());
+    }
+
+    #[tokio::test]
+    async fn unbuildable_endpoints_follow_partial_result_policy() {
+        // An identity without a valid private key can not be applied to any endpoint.
+        let tls_config = ClientTlsConfig::new().identity(tonic::transport::Identity::from_pem(
+            "",
+            "not a private key",
+        ));
+
+        let mut ignoring = probe(ChangeOrdering::default());
+        ignoring.state.set_tls_config(tls_config.clone());
+        let changeset = ignoring
+            .create_changeset(&addresses(&[1, 2]))
+            .await
+            .unwrap();
+        assert!(changeset.is_empty());
+
+        let mut failing = probe(ChangeOrdering::default());
+        failing.partial_result_policy = PartialResultPolicy::FailProbe;
+        failing.state.set_tls_config(tls_config);
+        assert!(matches!(
+            failing.create_changeset(&addresses(&[1, 2])).await,
+            Err(ProbeError::BuildEndpoint(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn skipped_endpoints_are_retried_by_the_next_probe() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+        let mut probe = probe(ChangeOrdering::default());
+        probe.endpoint_reporter = sender;
+        *probe.dns_lookup.0.lock().unwrap() = addresses(&[1]);
+        // Fail to build the first endpoint only, as a transient error would.
+        let failed = AtomicBool::new(false);
+        probe.endpoint_layer = Some(Box::new(move |_, endpoint: Endpoint| {
+            if failed.swap(true, Ordering::Relaxed) {
+                Ok(endpoint)
+            } else {
+                Err(anyhow::anyhow!("transient error"))
+            }
+        }));
+
+        probe.probe_once().await.unwrap();
+        assert!(receiver.try_recv().is_err());
+        assert!(probe.endpoints().is_empty());
+
+        probe.probe_once().await.unwrap();
+        assert!(matches!(receiver.try_recv(), Ok(Change::Insert(..))));
+        assert_eq!(&addresses(&[1]), probe.endpoints());
+    }
+
+    #[tokio::test]
+    async fn removals_are_deferred_until_confirmed() {
+        let mut probe = probe(ChangeOrdering::default());
+        probe.removal_confirmations = 3;
+        probe.endpoints = addresses(&[1, 2, 3]);
+
+        let mut resolve = |ports: &[u16]| {
+            let mut endpoints = addresses(ports);
+            probe.defer_removals(&mut endpoints);
+            probe.endpoints = endpoints.clone();
+            endpoints
+        };
+
+        assert_eq!(addresses(&[1, 2, 3]), resolve(&[1]));
+        // Reappearing resets the count of an endpoint.
+        assert_eq!(addresses(&[1, 2, 3]), resolve(&[1, 2]));
+        assert_eq!(addresses(&[1, 2]), resolve(&[1]));
+        assert_eq!(addresses(&[1, 2]), resolve(&[1]));
+        assert_eq!(addresses(&[1]), resolve(&[1]));
+        // Additions are never deferred.
+        assert_eq!(addresses(&[1, 4]), resolve(&[1, 4]));
+    }
+
+    #[tokio::test]
+    async fn new_endpoints_are_capped_while_committed_ones_are_kept() {
+        let mut probe = probe(ChangeOrdering::default());
+        probe.endpoints = addresses(&[1, 2]);
+
+        let mut endpoints = addresses(&[1, 2, 3, 4, 5]);
+        probe.cap_new_endpoints(&mut endpoints, 3);
+        assert_eq!(addresses(&[1, 2, 3]), endpoints);
+
+        // Removed endpoints make room for new ones.
+        let mut endpoints = addresses(&[2, 3, 4, 5]);
+        probe.cap_new_endpoints(&mut endpoints, 3);
+        assert_eq!(addresses(&[2, 3, 4]), endpoints);
+
+        // Committed endpoints are never dropped, even over the cap.
+        let mut endpoints = addresses(&[1, 2, 3]);
+        probe.cap_new_endpoints(&mut endpoints, 1);
+        assert_eq!(addresses(&[1, 2]), endpoints);
+    }
+
+    #[tokio::test]
+    async fn changeset_only_contains_the_changed_endpoints() {
+        let mut probe = probe(ChangeOrdering::default());
+        probe.endpoints = addresses(&[1, 2, 3]);
+
+        // A stable resolution does not touch any endpoint.
+        let changeset = probe
+            .create_changeset(&addresses(&[1, 2, 3]))
+            .await
+            .unwrap();
+        assert!(changeset.is_empty());
+
+        // Replacing one endpoint leaves the other ones alone.
+        let changeset = probe
+            .create_changeset(&addresses(&[1, 2, 4]))
+            .await
+            .unwrap();
+        let changed: Vec<_> = changeset
+            .iter()
+            .map(|change| match change {
+                Change::Insert(address, _) => (true, address.port()),
+                Change::Remove(address) => (false, address.port()),
+            })
+            .collect();
+        assert_eq!(vec![(true, 4), (false, 3)], changed);
+    }
+
+    #[tokio::test]
+    async fn endpoint_layer_errors_follow_partial_result_policy() {
+        let mut probe = probe(ChangeOrdering::default());
+        probe.partial_result_policy = PartialResultPolicy::FailProbe;
+        probe.endpoint_layer = Some(Box::new(|_, endpoint: Endpoint| {
+            endpoint
+                .user_agent("not\na valid header")
+                .map_err(anyhow::Error::from)
+        }));
+
+        assert!(matches!(
+            probe.create_changeset(&addresses(&[1])).await,
+            Err(ProbeError::BuildEndpoint(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unconnectable_addresses_are_skipped() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+        let mut probe = probe(ChangeOrdering::default());
+        probe.endpoint_reporter = sender;
+        let port_zero = SocketAddr::from(([127, 0, 0, 1], 0));
+        let unspecified = SocketAddr::from(([0, 0, 0, 0], 5000));
+        let mut endpoints = addresses(&[1]);
+        endpoints.extend([port_zero, unspecified]);
+        *probe.dns_lookup.0.lock().unwrap() = endpoints;
+
+        probe.probe_once().await.unwrap();
+
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(Change::Insert(address, _)) if address == SocketAddr::from(([127, 0, 0, 1], 1))
+        ));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(&addresses(&[1]), probe.endpoints());
+
+        // A resolution of unconnectable addresses only is an empty one.
+        *probe.dns_lookup.0.lock().unwrap() = HashSet::from([port_zero, unspecified]);
+        probe.probe_once().await.unwrap();
+        assert!(probe.endpoints().is_empty());
+        assert_eq!(
+            Some("port 0 can not be connected to"),
+            unconnectable_reason(&port_zero)
+        );
+        assert!(unconnectable_reason(&unspecified).is_some());
+    }
+
+    #[tokio::test]
+    async fn only_new_and_failing_endpoints_are_checked_for_ejection() {
+        // Accepts connections and drops them, failing every check.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicU64::new(0));
+        tokio::spawn({
+            let accepted = Arc::clone(&accepted);
+            async move {
+                while let Ok((connection, _)) = listener.accept().await {
+                    accepted.fetch_add(1, Ordering::SeqCst);
+                    drop(connection);
+                }
+            }
+        });
+        let mut probe = probe(ChangeOrdering::default());
+        let mut endpoints = HashSet::from([address]);
+
+        // A committed endpoint that passed its last check is left alone.
+        probe.endpoints = endpoints.clone();
+        probe.eject_unconnectable(&mut endpoints, 2).await;
+        assert_eq!(0, accepted.load(Ordering::SeqCst));
+        assert_eq!(HashSet::from([address]), endpoints);
+
+        // A new endpoint is checked.
+        probe.endpoints.clear();
+        probe.eject_unconnectable(&mut endpoints, 2).await;
+        let checked = accepted.load(Ordering::SeqCst);
+        assert!(checked > 0);
+        assert_eq!(HashSet::from([address]), endpoints);
+
+        // A failing endpoint is checked again once committed, and ejected.
+        probe.endpoints = endpoints.clone();
+        probe.eject_unconnectable(&mut endpoints, 2).await;
+        assert!(accepted.load(Ordering::SeqCst) > checked);
+        assert!(endpoints.is_empty());
+    }
+
+    #[tokio::test]
+    async fn scope_ids_are_kept_in_the_endpoint_uri() {
+        let probe = probe(ChangeOrdering::default());
+        let scoped = SocketAddr::V6(std::net::SocketAddrV6::new(
+            "fe80::1".parse().unwrap(),
+            5000,
+            0,
+            3,
+        ));
+        let unscoped = SocketAddr::V6(std::net::SocketAddrV6::new(
+            "fe80::1".parse().unwrap(),
+            5000,
+            0,
+            0,
+        ));
+
+        let uri = probe.build_endpoint(&scoped).unwrap().uri().clone();
+        assert_eq!(Some("[fe80::1%3]"), uri.host());
+        assert_eq!(Some(5000), uri.port_u16());
+        assert_eq!(
+            Some("[fe80::1]"),
+            probe.build_endpoint(&unscoped).unwrap().uri().host()
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint_uri_builder_replaces_the_default_uri() {
+        let mut probe = probe(ChangeOrdering::default());
+        probe.endpoint_uri_builder = Some(Box::new(|address: &SocketAddr| {
+            format!(
+                "http://{}.proxy.local:8080",
+                address.ip().to_string().replace('.', "-")
+            )
+            .parse()
+            .unwrap()
+        }));
+        probe.state.set_tls_config(ClientTlsConfig::new());
+
+        let endpoint = probe
+            .build_endpoint(&SocketAddr::from(([10, 0, 0, 1], 5000)))
+            .unwrap();
+        assert_eq!(
+            "http://10-0-0-1.proxy.local:8080/",
+            endpoint.uri().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint_layer_receives_the_address() {
+        let mut probe = probe(ChangeOrdering::default());
+        let slow = SocketAddr::from(([127, 0, 0, 2], 5000));
+        probe.endpoint_layer = Some(Box::new(move |address, endpoint: Endpoint| {
+            anyhow::ensure!(address != slow, "{} is rejected", address);
+            Ok(endpoint)
+        }));
+
+        assert!(probe
+            .build_endpoint(&SocketAddr::from(([127, 0, 0, 1], 5000)))
+            .is_ok());
+        let error = probe.build_endpoint(&slow).err().unwrap();
+        assert!(format!("{:#}", error).contains("127.0.0.2:5000 is rejected"));
+    }
+
+    #[tokio::test]
+    async fn tls_config_applies_to_the_endpoints_built_afterwards() {
+        let probe = probe(ChangeOrdering::default());
+        let address = SocketAddr::from(([127, 0, 0, 1], 5000));
+        assert_eq!(
+            Some("http"),
+            probe.build_endpoint(&address).unwrap().uri().scheme_str()
+        );
+
+        probe.state.set_tls_config(ClientTlsConfig::new());
+        assert_eq!(
+            Some("https"),
+            probe.build_endpoint(&address).unwrap().uri().scheme_str()
+        );
+    }
+
+    /// Resolves every service definition to its own address after a delay.
+    struct SlowLookup(tokio::time::Duration);
+
+    #[async_trait::async_trait]
+    impl LookupService for SlowLookup {
+        async fn resolve_service_endpoints(
+            &self,
+            definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+            tokio::time::sleep(self.0).await;
+            Ok(HashSet::from([SocketAddr::from((
+                [127, 0, 0, 1],
+                definition.port(),
+            ))]))
+        }
+    }
+
+    #[tokio::test]
+    async fn service_definitions_are_resolved_concurrently() {
+        let delay = tokio::time::Duration::from_millis(200);
+        let (sender, _) = tokio::sync::mpsc::channel(16);
+        let state = Arc::new(ProbeState::new(
+            ServiceDefinition::from_parts("test", 1).unwrap(),
+        ));
+        for port in [2, 3, 4] {
+            state.add_service_definition(ServiceDefinition::from_parts("test", port).unwrap());
+        }
+        let probe = GrpcServiceProbe::new_with_reporter(
+            GrpcServiceProbeConfig {
+                state,
+                dns_lookup: SlowLookup(delay),
+                probe_interval: tokio::time::Duration::from_secs(10),
+                endpoint_timeout: None,
+                endpoint_connect_timeout: None,
+                http2_adaptive_window: None,
+                endpoint_layer: None,
+                change_ordering: ChangeOrdering::default(),
+                partial_result_policy: PartialResultPolicy::default(),
+                full_buffer_policy: FullBufferPolicy::default(),
+                tcp_precheck: false,
+                grpc_precheck: false,
+                observer: None,
+                snapshot_log_interval: None,
+                on_endpoints_available: None,
+                on_endpoints_exhausted: None,
+                balancing_seed: None,
+                change_report_threshold: None,
+                eject_unconnectable_after: None,
+                adaptive_interval: None,
+                endpoint_uri_builder: None,
+                initial_probe_jitter: None,
+                max_endpoints_hard_limit: None,
+                log_probe_errors: true,
+                on_consecutive_failures: None,
+                zero_downtime_cutover: false,
+                max_open_connections: None,
+                removal_confirmations: 1,
+                min_healthy_warn: None,
+                address_rewrite: None,
+            },
+            sender,
+        );
+
+        let start = tokio::time::Instant::now();
+        let resolution = probe.resolve_endpoints().await.unwrap();
+
+        assert_eq!(addresses(&[1, 2, 3, 4]), resolution.endpoints);
+        // Resolving the definitions one after the other would take four times the delay.
+        assert!(start.elapsed() < delay * 2);
+    }
+
+    /// Accept connections on a local port, answering every connection with `response`.
+    async fn respond_with(response: &'static [u8]) -> SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let _ = stream.write_all(response).await;
+                    let _ = stream.read(&mut [0; 1024]).await;
+                });
+            }
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn tls_mismatches_are_hinted_at() {
+        // A plaintext HTTP/1.1 response, and the TLS alert of a server receiving plaintext.
+        let plaintext = respond_with(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+        let tls = respond_with(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x46]).await;
+
+        let plaintext_probe = probe(ChangeOrdering::default());
+        let err = plaintext_probe.check_grpc(&tls).await.unwrap_err();
+        assert!(tls_mismatch_hint(false, err.as_ref()).contains("is it expecting TLS?"));
+
+        let tls_probe = probe(ChangeOrdering::default());
+        tls_probe.state.set_tls_config(ClientTlsConfig::new());
+        let err = tls_probe.check_grpc(&plaintext).await.unwrap_err();
+        assert!(
+            tls_mismatch_hint(true, err.as_ref()).contains("is the endpoint serving plaintext?")
+        );
+
+        let unrelated = anyhow::anyhow!("connection refused");
+        assert_eq!(tls_mismatch_hint(true, unrelated.as_ref()), "");
+    }
+
+    #[test]
+    fn random_delays_are_spread_below_the_maximum() {
+        let max = tokio::time::Duration::from_secs(1);
+        let delays: HashSet<_> = (0..10).map(|_| random_delay(max, None)).collect();
+
+        assert!(delays.iter().all(|delay| *delay <= max));
+        assert!(delays.len() > 1);
+    }
+
+    #[test]
+    fn seeded_random_delays_are_reproducible() {
+        let max = tokio::time::Duration::from_secs(1);
+        let service = ServiceDefinition::from_parts("test", 5000).unwrap();
+        let other_service = ServiceDefinition::from_parts("other", 5000).unwrap();
+
+        let delay = random_delay(max, Some((42, &service)));
+
+        assert!(delay <= max);
+        assert_eq!(delay, random_delay(max, Some((42, &service))));
+        assert_ne!(delay, random_delay(max, Some((43, &service))));
+        assert_ne!(delay, random_delay(max, Some((42, &other_service))));
+    }
+
+    #[test]
+    fn adaptive_interval_follows_the_changes() {
+        let mut probe = probe(ChangeOrdering::default());
+        let (min, max) = (
+            tokio::time::Duration::from_secs(1),
+            tokio::time::Duration::from_secs(8),
+        );
+        probe.adaptive_interval = Some((min, max));
+        probe.adaptive_current = max;
+
+        let intervals: Vec<_> = [Some(3), Some(0), Some(0), None, Some(0), Some(0), Some(1)]
+            .into_iter()
+            .map(|changes| {
+                probe.last_changeset_len = changes;
+                probe.next_interval().as_secs()
+            })
+            .collect();
 
-        Some(endpoint)
+        assert_eq!(vec![1, 2, 4, 4, 8, 8, 1], intervals);
     }
 }
@@ -0,0 +1,55 @@
+//! Implements a [`LookupService`] over a fixed set of endpoints.
+
+use crate::{LookupService, ServiceDefinition};
+use std::{collections::HashSet, net::SocketAddr};
+
+/// Implements [`LookupService`] by always resolving to the same set of addresses,
+/// without any DNS query.
+///
+/// The [`ServiceDefinition`] is ignored, since the addresses carry their own port.
+/// [`LoadBalancedChannel::from_endpoints`](crate::LoadBalancedChannel::from_endpoints)
+/// builds a channel over such a set in one go.
+///
+/// ```rust
+/// #[tokio::main]
+/// async fn main() {
+///     use ginepro::{LoadBalancedChannel, StaticLookupService};
+///
+///     let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///         .lookup_service(StaticLookupService::new([
+///             "10.0.0.1:5000".parse().unwrap(),
+///             "10.0.0.2:5000".parse().unwrap(),
+///         ]))
+///         .channel()
+///         .await
+///         .expect("failed to construct LoadBalancedChannel");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticLookupService {
+    endpoints: HashSet<SocketAddr>,
+}
+
+impl StaticLookupService {
+    /// Resolve every service definition to `endpoints`.
+    pub fn new(endpoints: impl IntoIterator<Item = SocketAddr>) -> Self {
+        Self {
+            endpoints: endpoints.into_iter().collect(),
+        }
+    }
+
+    /// Get the endpoints every service definition resolves to.
+    pub fn endpoints(&self) -> &HashSet<SocketAddr> {
+        &self.endpoints
+    }
+}
+
+#[async_trait::async_trait]
+impl LookupService for StaticLookupService {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        Ok(self.endpoints.clone())
+    }
+}
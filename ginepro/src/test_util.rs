@@ -0,0 +1,342 @@
+//! Helpers to test the load balancing of a [`LoadBalancedChannel`],
+//! available with the `test-util` feature.
+
+use crate::{LoadBalancedChannel, LookupService, ServiceDefinition};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// The number of calls answered by every server, collected by [`call_histogram`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallHistogram<K: Eq + Hash> {
+    counts: HashMap<K, usize>,
+    calls: usize,
+}
+
+impl<K: Eq + Hash + std::fmt::Debug> CallHistogram<K> {
+    /// Get the number of calls answered by every server.
+    pub fn counts(&self) -> &HashMap<K, usize> {
+        &self.counts
+    }
+
+    /// Get the total number of calls.
+    pub fn calls(&self) -> usize {
+        self.calls
+    }
+
+    /// Assert that exactly `servers` servers answered, each of them answering
+    /// `calls / servers` calls give or take `tolerance`, e.g. `0.5` for 50%.
+    ///
+    /// Balancing picks endpoints at random, so the tolerance has to allow for
+    /// the variance of the number of calls: the fewer the calls, the larger it gets.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the histogram if the calls are not spread as expected.
+    pub fn assert_fair(&self, servers: usize, tolerance: f64) {
+        assert_eq!(
+            servers,
+            self.counts.len(),
+            "expected {} servers to answer, got {:?}",
+            servers,
+            self.counts
+        );
+
+        let expected = self.calls as f64 / servers as f64;
+        for (server, count) in &self.counts {
+            let deviation = (*count as f64 - expected).abs() / expected;
+            assert!(
+                deviation <= tolerance,
+                "{:?} answered {} calls instead of {:.0} ± {:.0}%: {:?}",
+                server,
+                count,
+                expected,
+                tolerance * 100.0,
+                self.counts
+            );
+        }
+    }
+}
+
+/// Make `calls` calls one after the other with `call`, which is given a clone of `channel`
+/// and returns the identity of the server that answered, and count the calls answered
+/// by every server.
+///
+/// Fails on the first failed call.
+///
+/// ```rust,no_run
+/// # async fn run() -> Result<(), tonic::Status> {
+/// use ginepro::{test_util::call_histogram, LoadBalancedChannel};
+/// use shared_proto::pb::{tester_client::TesterClient, Ping};
+///
+/// let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///     .channel()
+///     .await
+///     .expect("failed to construct LoadBalancedChannel");
+///
+/// let histogram = call_histogram(&load_balanced_channel, 100, |channel| async move {
+///     let pong = TesterClient::new(channel).test(Ping {}).await?;
+///     Ok::<_, tonic::Status>(format!("{:?}", pong.into_inner().payload))
+/// })
+/// .await?;
+/// histogram.assert_fair(3, 0.5);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn call_histogram<F, Fut, K, E>(
+    channel: &LoadBalancedChannel,
+    calls: usize,
+    mut call: F,
+) -> Result<CallHistogram<K>, E>
+where
+    F: FnMut(LoadBalancedChannel) -> Fut,
+    Fut: Future<Output = Result<K, E>>,
+    K: Eq + Hash,
+{
+    let mut counts = HashMap::new();
+    for _ in 0..calls {
+        let server = call(channel.clone()).await?;
+        *counts.entry(server).or_insert(0) += 1;
+    }
+
+    Ok(CallHistogram { counts, calls })
+}
+
+/// What a [`ScriptedLookupService`] answers with.
+#[derive(Debug, Clone)]
+enum ScriptedResult {
+    Resolve(HashSet<SocketAddr>),
+    Fail,
+}
+
+/// A sequence of results for a [`ScriptedLookupService`] to go through, each of them
+/// held for a while, e.g. a full set of endpoints, then an empty one, then the full set again.
+#[derive(Debug, Clone, Default)]
+pub struct FlapScript {
+    steps: Vec<(ScriptedResult, Duration)>,
+}
+
+impl FlapScript {
+    /// Start an empty script.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve to `endpoints` for `hold`.
+    pub fn resolve(
+        mut self,
+        endpoints: impl IntoIterator<Item = SocketAddr>,
+        hold: Duration,
+    ) -> Self {
+        self.steps.push((
+            ScriptedResult::Resolve(endpoints.into_iter().collect()),
+            hold,
+        ));
+        self
+    }
+
+    /// Resolve to an empty set of endpoints for `hold`.
+    pub fn empty(self, hold: Duration) -> Self {
+        self.resolve([], hold)
+    }
+
+    /// Fail every resolution for `hold`.
+    pub fn fail(mut self, hold: Duration) -> Self {
+        self.steps.push((ScriptedResult::Fail, hold));
+        self
+    }
+}
+
+/// A [`LookupService`] whose results are set by the test, by hand or by playing a [`FlapScript`].
+///
+/// Clones share their results, so the test keeps a clone to drive the one given to the channel.
+///
+/// ```rust,no_run
+/// # async fn run() {
+/// use ginepro::test_util::{calls_during, FlapScript, ScriptedLookupService};
+/// use ginepro::LoadBalancedChannel;
+/// use shared_proto::pb::{tester_client::TesterClient, Ping};
+/// use std::time::Duration;
+///
+/// let endpoints = ["127.0.0.1:5000".parse().unwrap()];
+/// let lookup_service = ScriptedLookupService::new(endpoints);
+/// let load_balanced_channel = LoadBalancedChannel::builder(("my.hostname", 5000))
+///     .lookup_service(lookup_service.clone())
+///     .dns_probe_interval(Duration::from_millis(10))
+///     .removal_confirmations(5)
+///     .overall_timeout(Duration::from_millis(100))
+///     .channel()
+///     .await
+///     .expect("failed to construct LoadBalancedChannel");
+///
+/// let script = FlapScript::new()
+///     .empty(Duration::from_millis(20))
+///     .resolve(endpoints, Duration::from_millis(100));
+/// let outcomes = calls_during(
+///     &load_balanced_channel,
+///     lookup_service.play(script),
+///     Duration::from_millis(5),
+///     |channel| async move { TesterClient::new(channel).test(Ping {}).await },
+/// )
+/// .await;
+/// outcomes.assert_no_failures();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScriptedLookupService {
+    result: Arc<Mutex<ScriptedResult>>,
+    resolutions: Arc<AtomicUsize>,
+}
+
+impl ScriptedLookupService {
+    /// Resolve every service definition to `endpoints` until told otherwise.
+    pub fn new(endpoints: impl IntoIterator<Item = SocketAddr>) -> Self {
+        Self {
+            result: Arc::new(Mutex::new(ScriptedResult::Resolve(
+                endpoints.into_iter().collect(),
+            ))),
+            resolutions: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Resolve every service definition to `endpoints` from now on.
+    pub fn set_endpoints(&self, endpoints: impl IntoIterator<Item = SocketAddr>) {
+        self.set(ScriptedResult::Resolve(endpoints.into_iter().collect()));
+    }
+
+    /// Fail every resolution from now on.
+    pub fn set_failing(&self) {
+        self.set(ScriptedResult::Fail);
+    }
+
+    /// Get the number of resolutions so far, successful or not.
+    pub fn resolutions(&self) -> usize {
+        self.resolutions.load(Ordering::SeqCst)
+    }
+
+    /// Go through the steps of `script`, completing once the last one has been held.
+    ///
+    /// The steps are held on the tokio clock, so a paused clock steps through them instantly.
+    pub async fn play(&self, script: FlapScript) {
+        for (result, hold) in script.steps {
+            self.set(result);
+            tokio::time::sleep(hold).await;
+        }
+    }
+
+    fn set(&self, result: ScriptedResult) {
+        *self.result.lock().expect("scripted result lock poisoned") = result;
+    }
+}
+
+#[async_trait::async_trait]
+impl LookupService for ScriptedLookupService {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.resolutions.fetch_add(1, Ordering::SeqCst);
+        match &*self.result.lock().expect("scripted result lock poisoned") {
+            ScriptedResult::Resolve(endpoints) => Ok(endpoints.clone()),
+            ScriptedResult::Fail => Err(anyhow::anyhow!("scripted resolution failure")),
+        }
+    }
+}
+
+/// The outcomes of the calls made by [`calls_during`].
+#[derive(Debug)]
+pub struct CallOutcomes<E> {
+    succeeded: usize,
+    failures: Vec<E>,
+}
+
+impl<E: std::fmt::Debug> CallOutcomes<E> {
+    /// Get the number of successful calls.
+    pub fn succeeded(&self) -> usize {
+        self.succeeded
+    }
+
+    /// Get the errors of the failed calls, in the order they failed.
+    pub fn failures(&self) -> &[E] {
+        &self.failures
+    }
+
+    /// Assert that calls were made, and that none of them failed.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the errors if any call failed.
+    pub fn assert_no_failures(&self) {
+        assert!(self.succeeded > 0, "no call was made");
+        assert!(
+            self.failures.is_empty(),
+            "{} calls failed while {} succeeded: {:?}",
+            self.failures.len(),
+            self.succeeded,
+            self.failures
+        );
+    }
+
+    /// Assert that at least one call failed, e.g. while the endpoints were gone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every call succeeded.
+    pub fn assert_some_failures(&self) {
+        assert!(
+            !self.failures.is_empty(),
+            "all {} calls succeeded",
+            self.succeeded
+        );
+    }
+}
+
+/// Keep making calls with `call`, which is given a clone of `channel`, `interval` apart
+/// until `until` completes, e.g. while a [`ScriptedLookupService`] plays a [`FlapScript`],
+/// and collect their outcomes.
+///
+/// A call still in flight when `until` completes is dropped and not counted. Calls wait for
+/// an endpoint rather than fail while the channel has none, so bound them with a timeout,
+/// e.g. [`LoadBalancedChannelBuilder::overall_timeout`](crate::LoadBalancedChannelBuilder::overall_timeout),
+/// for the missing endpoints to show up as failures.
+pub async fn calls_during<F, Fut, T, E>(
+    channel: &LoadBalancedChannel,
+    until: impl Future<Output = ()>,
+    interval: Duration,
+    mut call: F,
+) -> CallOutcomes<E>
+where
+    F: FnMut(LoadBalancedChannel) -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut outcomes = CallOutcomes {
+        succeeded: 0,
+        failures: Vec::new(),
+    };
+
+    tokio::pin!(until);
+    loop {
+        let next = async {
+            let result = call(channel.clone()).await;
+            tokio::time::sleep(interval).await;
+            result
+        };
+        tokio::select! {
+            _ = &mut until => break,
+            result = next => match result {
+                Ok(_) => outcomes.succeeded += 1,
+                Err(err) => outcomes.failures.push(err),
+            },
+        }
+    }
+
+    outcomes
+}
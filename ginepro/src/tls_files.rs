@@ -0,0 +1,58 @@
+//! Reads the PEM files of a tls configuration.
+
+use anyhow::Context;
+use std::path::Path;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+/// Build a [`ClientTlsConfig`] trusting the certificate authority at `ca_path`, if any,
+/// and presenting the client certificate and key at `identity`, if any.
+///
+/// The files are checked to hold PEM certificates and keys, so that a misconfiguration
+/// is reported here rather than when the endpoints are connected to.
+pub(crate) fn client_tls_config(
+    ca_path: Option<&Path>,
+    identity: Option<(&Path, &Path)>,
+) -> Result<ClientTlsConfig, anyhow::Error> {
+    let mut tls_config = ClientTlsConfig::new();
+
+    if let Some(ca_path) = ca_path {
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(read_certificates(ca_path)?));
+    }
+    if let Some((certificate_path, key_path)) = identity {
+        tls_config = tls_config.identity(Identity::from_pem(
+            read_certificates(certificate_path)?,
+            read_private_key(key_path)?,
+        ));
+    }
+
+    Ok(tls_config)
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    std::fs::read(path).with_context(|| format!("failed to read '{}'", path.display()))
+}
+
+/// Read a PEM file holding at least one certificate.
+fn read_certificates(path: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    let pem = read_pem(path)?;
+
+    let certificates = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("'{}' is not a valid PEM file", path.display()))?;
+    if certificates.is_empty() {
+        anyhow::bail!("'{}' does not hold any PEM certificate", path.display());
+    }
+
+    Ok(pem)
+}
+
+/// Read a PEM file holding a private key.
+fn read_private_key(path: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    let pem = read_pem(path)?;
+
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .with_context(|| format!("'{}' is not a valid PEM file", path.display()))?
+        .with_context(|| format!("'{}' does not hold any PEM private key", path.display()))?;
+
+    Ok(pem)
+}
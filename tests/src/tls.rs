@@ -74,7 +74,8 @@ fn generate_ssl_certificate(private_key: &Rsa<Private>) -> X509 {
         .set_serial_number(&serial.to_asn1_integer().unwrap())
         .unwrap();
 
-    let basic_constraints = BasicConstraints::new().critical().ca().build().unwrap();
+    // The certificate is trusted directly, and rustls rejects CA certificates used by servers.
+    let basic_constraints = BasicConstraints::new().critical().build().unwrap();
     builder.append_extension(basic_constraints).unwrap();
     let key_usage = KeyUsage::new()
         .digital_signature()
@@ -0,0 +1,78 @@
+use ginepro::{DnsRecordKind, DnsResolver, LookupService, ServiceDefinition};
+use std::{collections::HashSet, net::SocketAddr};
+
+#[tokio::test]
+async fn dns_resolver_merges_the_ips_of_all_hostnames() {
+    // IP literals are resolved without querying a name server.
+    let definition =
+        ServiceDefinition::from_hostnames(["127.0.0.1".to_string(), "127.0.0.2".to_string()], 5000)
+            .unwrap();
+    let resolver = DnsResolver::from_system_config()
+        .await
+        .expect("failed to read the system dns configuration");
+
+    let endpoints = resolver
+        .resolve_service_endpoints(&definition)
+        .await
+        .expect("failed to resolve");
+
+    assert_eq!(
+        HashSet::from([
+            SocketAddr::from(([127, 0, 0, 1], 5000)),
+            SocketAddr::from(([127, 0, 0, 2], 5000)),
+        ]),
+        endpoints
+    );
+}
+
+#[tokio::test]
+async fn srv_resolution_falls_back_to_the_ips_without_srv_records() {
+    // Localhost names never have SRV records, and are resolved without querying a name server.
+    let definition = ServiceDefinition::from_parts("localhost", 5000).unwrap();
+    let resolver = DnsResolver::from_system_config()
+        .await
+        .expect("failed to read the system dns configuration")
+        .with_record_kind(DnsRecordKind::SrvWithIpFallback);
+
+    let endpoints = resolver
+        .resolve_service_endpoints(&definition)
+        .await
+        .expect("failed to resolve");
+
+    assert!(endpoints.contains(&SocketAddr::from(([127, 0, 0, 1], 5000))));
+}
+
+#[tokio::test]
+async fn dns_resolutions_carry_the_expiry_of_the_records() {
+    let definition = ServiceDefinition::from_parts("localhost", 5000).unwrap();
+    let resolver = DnsResolver::from_system_config()
+        .await
+        .expect("failed to read the system dns configuration");
+
+    let resolution = resolver
+        .resolve_service_endpoints_with_metadata(&definition)
+        .await
+        .expect("failed to resolve");
+
+    assert!(resolution
+        .endpoints
+        .contains(&SocketAddr::from(([127, 0, 0, 1], 5000))));
+    assert!(resolution.valid_until.is_some());
+    assert_eq!(None, resolution.refresh_after);
+}
+
+#[tokio::test]
+async fn srv_resolution_fails_without_srv_records() {
+    let definition = ServiceDefinition::from_parts("localhost", 5000).unwrap();
+    let resolver = DnsResolver::from_system_config()
+        .await
+        .expect("failed to read the system dns configuration")
+        .with_record_kind(DnsRecordKind::Srv);
+
+    let error = resolver
+        .resolve_service_endpoints(&definition)
+        .await
+        .expect_err("localhost has no srv records");
+
+    assert!(format!("{:#}", error).contains("localhost"));
+}
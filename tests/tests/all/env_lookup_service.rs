@@ -0,0 +1,80 @@
+use ginepro::{EnvLookupService, LookupService, ServiceDefinition};
+use std::{collections::HashSet, net::SocketAddr};
+
+// Every test uses its own variable, since the environment is shared by the whole process.
+
+fn definition() -> ServiceDefinition {
+    ServiceDefinition::from_parts("test", 5000).unwrap()
+}
+
+#[tokio::test]
+async fn addresses_are_separated_by_commas_and_whitespace() {
+    std::env::set_var(
+        "GINEPRO_TEST_SEPARATORS",
+        "127.0.0.1:5000,127.0.0.2:5001, 127.0.0.3:5002\n[::1]:5003",
+    );
+    let lookup = EnvLookupService::new("GINEPRO_TEST_SEPARATORS");
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&definition())
+        .await
+        .unwrap();
+
+    let expected: HashSet<SocketAddr> = [
+        "127.0.0.1:5000",
+        "127.0.0.2:5001",
+        "127.0.0.3:5002",
+        "[::1]:5003",
+    ]
+    .iter()
+    .map(|address| address.parse().unwrap())
+    .collect();
+    assert_eq!(expected, endpoints);
+}
+
+#[tokio::test]
+async fn malformed_addresses_are_skipped() {
+    std::env::set_var(
+        "GINEPRO_TEST_MALFORMED",
+        "127.0.0.1:5000, not-an-address, 127.0.0.2, 127.0.0.3:5002",
+    );
+    let lookup = EnvLookupService::new("GINEPRO_TEST_MALFORMED");
+
+    let endpoints = lookup
+        .resolve_service_endpoints(&definition())
+        .await
+        .unwrap();
+
+    let expected: HashSet<SocketAddr> = ["127.0.0.1:5000", "127.0.0.3:5002"]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect();
+    assert_eq!(expected, endpoints);
+}
+
+#[tokio::test]
+async fn the_variable_is_read_on_every_resolution() {
+    let lookup = EnvLookupService::new("GINEPRO_TEST_RELOAD");
+
+    std::env::remove_var("GINEPRO_TEST_RELOAD");
+    assert!(lookup
+        .resolve_service_endpoints(&definition())
+        .await
+        .is_err());
+
+    std::env::set_var("GINEPRO_TEST_RELOAD", "127.0.0.1:5000");
+    assert_eq!(
+        HashSet::from(["127.0.0.1:5000".parse().unwrap()]),
+        lookup
+            .resolve_service_endpoints(&definition())
+            .await
+            .unwrap()
+    );
+
+    std::env::set_var("GINEPRO_TEST_RELOAD", "");
+    assert!(lookup
+        .resolve_service_endpoints(&definition())
+        .await
+        .unwrap()
+        .is_empty());
+}
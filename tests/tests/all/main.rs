@@ -1,2 +1,5 @@
+mod dns_resolver;
+mod env_lookup_service;
 pub mod lookup;
+mod memoizing_lookup_service;
 mod service_probe;
@@ -0,0 +1,106 @@
+use crate::lookup::TestDnsResolver;
+use ginepro::{LookupService, MemoizingLookupService, Resolution, ServiceDefinition};
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// Counts the resolutions delegated to a [`TestDnsResolver`].
+struct CountingLookup {
+    resolver: TestDnsResolver,
+    resolutions: AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl LookupService for CountingLookup {
+    async fn resolve_service_endpoints(
+        &self,
+        definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.resolutions.fetch_add(1, Ordering::SeqCst);
+        self.resolver.resolve_service_endpoints(definition).await
+    }
+}
+
+#[tokio::test]
+async fn memoized_resolutions_are_reused_until_the_ttl_elapses() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".into(), "127.0.0.1:5000".into())
+        .await;
+    let lookup = MemoizingLookupService::new(
+        CountingLookup {
+            resolver: resolver.clone(),
+            resolutions: AtomicUsize::new(0),
+        },
+        Duration::from_millis(200),
+    );
+    let definition = ServiceDefinition::from_parts("test", 5000).unwrap();
+
+    let first = lookup.resolve_service_endpoints(&definition).await.unwrap();
+    resolver
+        .add_ip_without_server("server_b".into(), "127.0.0.2:5000".into())
+        .await;
+    let memoized = lookup.resolve_service_endpoints(&definition).await.unwrap();
+
+    assert_eq!(first, memoized);
+    assert_eq!(1, lookup.inner().resolutions.load(Ordering::SeqCst));
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    let refreshed = lookup.resolve_service_endpoints(&definition).await.unwrap();
+
+    assert_eq!(2, refreshed.len());
+    assert_eq!(2, lookup.inner().resolutions.load(Ordering::SeqCst));
+}
+
+/// Resolves to a single endpoint, hinting when to refresh it and when it expires.
+struct HintingLookup {
+    valid_until: std::time::Instant,
+}
+
+#[async_trait::async_trait]
+impl LookupService for HintingLookup {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        unreachable!("the memoizing lookup service resolves with metadata")
+    }
+
+    async fn resolve_service_endpoints_with_metadata(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<Resolution, anyhow::Error> {
+        Ok(
+            Resolution::new(HashSet::from(["127.0.0.1:5000".parse().unwrap()]))
+                .with_refresh_after(Duration::from_millis(10))
+                .with_valid_until(self.valid_until),
+        )
+    }
+}
+
+#[tokio::test]
+async fn memoized_resolutions_keep_their_hints() {
+    let valid_until = std::time::Instant::now() + Duration::from_secs(30);
+    let ttl = Duration::from_secs(1);
+    let lookup = MemoizingLookupService::new(HintingLookup { valid_until }, ttl);
+    let definition = ServiceDefinition::from_parts("test", 5000).unwrap();
+
+    let first = lookup
+        .resolve_service_endpoints_with_metadata(&definition)
+        .await
+        .unwrap();
+    assert_eq!(Some(Duration::from_millis(10)), first.refresh_after);
+    assert_eq!(Some(valid_until), first.valid_until);
+
+    // Refreshing before the memoized resolution expires would return it again.
+    let memoized = lookup
+        .resolve_service_endpoints_with_metadata(&definition)
+        .await
+        .unwrap();
+    assert_eq!(first.endpoints, memoized.endpoints);
+    assert_eq!(Some(valid_until), memoized.valid_until);
+    assert!(memoized.refresh_after.unwrap() > ttl / 2);
+}
@@ -3,11 +3,16 @@ use crate::lookup::TesterImpl;
 use ginepro::{LoadBalancedChannel, LoadBalancedChannelBuilder, LookupService, ServiceDefinition};
 use shared_proto::pb::pong::Payload;
 use shared_proto::pb::tester_client::TesterClient;
+use shared_proto::pb::tester_server::TesterServer;
 use shared_proto::pb::Ping;
 use std::sync::Arc;
-use std::{collections::HashSet, net::SocketAddr};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
 use std::{net::AddrParseError, time::Duration};
-use tokio::sync::Mutex;
+use tests::test_server::TestServer;
+use tokio::sync::{mpsc::Receiver, Mutex};
 
 fn get_payload_raw(payload: Payload) -> String {
     match payload {
@@ -15,6 +20,52 @@ fn get_payload_raw(payload: Payload) -> String {
     }
 }
 
+/// Start a server that accepts connections but never answers.
+async fn start_unresponsive_server() -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+    address
+}
+
+/// Start a plain HTTP/1 server that rejects every connection with a `400 Bad Request`.
+async fn start_http1_server() -> SocketAddr {
+    use tokio::io::AsyncWriteExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut connection, _)) = listener.accept().await {
+            let _ = connection
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .await;
+        }
+    });
+    address
+}
+
+/// Do `num_calls` gRPC calls and collect the names of the servers that were called.
+async fn call_servers(
+    client: &mut TesterClient<LoadBalancedChannel>,
+    receiver: &mut Receiver<String>,
+    num_calls: usize,
+) -> HashSet<String> {
+    let mut servers_called = HashSet::new();
+    for _ in 0..num_calls {
+        client
+            .test(tonic::Request::new(Ping {}))
+            .await
+            .expect("failed to call server");
+        servers_called.insert(receiver.recv().await.expect(""));
+    }
+    servers_called
+}
+
 #[tokio::test]
 async fn load_balance_succeeds_with_churn() {
     // Steps:
@@ -289,7 +340,7 @@ async fn builder_and_resolve_shall_fail_on_error() {
         }
     }
 
-    LoadBalancedChannel::builder(("www.test.com", 5000))
+    let err = LoadBalancedChannel::builder(("www.test.com", 5000))
         .lookup_service(FailResolve)
         .timeout(tokio::time::Duration::from_millis(500))
         .resolution_strategy(ginepro::ResolutionStrategy::Eager {
@@ -298,6 +349,48 @@ async fn builder_and_resolve_shall_fail_on_error() {
         .channel()
         .await
         .unwrap_err();
+
+    // The error identifies the service that failed to resolve.
+    assert!(format!("{:#}", err).contains("www.test.com:5000"));
+}
+
+#[tokio::test]
+async fn box_error_lookup_errors_are_kept_in_the_chain_of_sources() {
+    struct FailResolve;
+    #[async_trait::async_trait]
+    impl ginepro::BoxErrorLookupService for FailResolve {
+        async fn resolve_service_endpoints(
+            &self,
+            _definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, Box<dyn std::error::Error + Send + Sync>> {
+            Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "could not reach dns").into())
+        }
+    }
+
+    let err: Box<dyn std::error::Error + Send + Sync> =
+        LoadBalancedChannel::builder(("www.test.com", 5000))
+            .lookup_service(ginepro::BoxErrorLookup::new(FailResolve))
+            .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+                timeout: Duration::from_secs(20),
+            })
+            .channel()
+            .await
+            .unwrap_err()
+            .into();
+
+    let mut messages = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    assert!(
+        messages
+            .iter()
+            .any(|message| message == "could not reach dns"),
+        "{:?}",
+        messages
+    );
 }
 
 #[tokio::test]
@@ -328,3 +421,2344 @@ async fn builder_and_resolve_shall_succeed_when_ips_are_returned() {
             .is_ok()
     );
 }
+
+#[tokio::test]
+async fn load_balance_over_dynamically_registered_service_definitions() {
+    // Steps:
+    //  1. Start two servers, each behind its own hostname.
+    //  2. Build a channel for the first hostname and see only that server called.
+    //  3. Register the second hostname and see both servers called.
+    //  4. Unregister the first hostname and see only the second server called.
+    // What we want to test:
+    //  A single channel tracks the union of its registered service definitions.
+    #[derive(Clone, Default)]
+    struct HostnameResolver {
+        hosts: Arc<std::sync::RwLock<HashMap<String, SocketAddr>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LookupService for HostnameResolver {
+        async fn resolve_service_endpoints(
+            &self,
+            definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+            Ok(self
+                .hosts
+                .read()
+                .unwrap()
+                .get(definition.hostname())
+                .copied()
+                .into_iter()
+                .collect())
+        }
+    }
+
+    let num_calls = 20;
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(num_calls);
+    let sender = Arc::new(Mutex::new(sender));
+    let resolver = HostnameResolver::default();
+    let probe_interval = tokio::time::Duration::from_millis(3);
+
+    let mut servers = Vec::new();
+    for (hostname, name) in [("blue.test", "server_a"), ("green.test", "server_b")] {
+        let server = TestServer::start(
+            TesterServer::new(TesterImpl {
+                sender: Arc::clone(&sender),
+                name: name.to_string(),
+            }),
+            None,
+            None,
+        )
+        .await;
+        resolver.hosts.write().unwrap().insert(
+            hostname.to_string(),
+            server.address().parse().expect("not a valid ip address"),
+        );
+        servers.push(server);
+    }
+
+    let load_balanced_channel = LoadBalancedChannel::builder(("blue.test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(probe_interval)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, num_calls).await
+    );
+
+    load_balanced_channel
+        .add_service_definition(("green.test", 5000))
+        .expect("invalid service definition");
+    tokio::time::sleep(probe_interval * 5).await;
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string(), "server_b".to_string()]),
+        call_servers(&mut client, &mut receiver, num_calls).await
+    );
+
+    assert!(load_balanced_channel
+        .remove_service_definition(&ServiceDefinition::from_parts("blue.test", 5000).unwrap()));
+    tokio::time::sleep(probe_interval * 5).await;
+
+    assert_eq!(
+        HashSet::from(["server_b".to_string()]),
+        call_servers(&mut client, &mut receiver, num_calls).await
+    );
+}
+
+#[tokio::test]
+async fn per_endpoint_channels_reach_every_endpoint() {
+    // Steps:
+    //  1. Create 3 servers that are added to the list of endpoints.
+    //  2. Do one gRPC call through each per-endpoint channel.
+    //  3. Assert that every server has been called exactly once.
+    // What we want to test:
+    //  Callers can fan out a request to every discovered endpoint.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    let servers = ["server_a", "server_b", "server_c"];
+
+    for server in servers {
+        resolver
+            .add_server_with_provided_impl(
+                server.to_string(),
+                TesterImpl {
+                    sender: Arc::clone(&sender),
+                    name: server.to_string(),
+                },
+            )
+            .await;
+    }
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let endpoint_channels = load_balanced_channel.per_endpoint_channels();
+    assert_eq!(servers.len(), endpoint_channels.len());
+
+    let mut servers_called = Vec::new();
+    for (_, channel) in endpoint_channels {
+        TesterClient::new(channel)
+            .test(tonic::Request::new(Ping {}))
+            .await
+            .expect("failed to call server");
+        servers_called.push(receiver.recv().await.expect(""));
+    }
+
+    servers_called.sort();
+    assert_eq!(servers.to_vec(), servers_called);
+}
+
+#[tokio::test]
+async fn tcp_precheck_skips_endpoints_without_a_server() {
+    // Scenario:
+    // The DNS probe returns an IP that we fail to connect to alongside a good server.
+    // With the TCP precheck enabled only the good server is reported, so every call
+    // succeeds and the ghost server never becomes an endpoint.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+
+    resolver
+        .add_ip_without_server("ghost_server".into(), "127.0.0.124:5000".into())
+        .await;
+    resolver
+        .add_server_with_provided_impl(
+            "good_server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "good_server".to_string(),
+            },
+        )
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .connect_timeout(Duration::from_millis(100))
+        .tcp_precheck(true)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    assert_eq!(1, load_balanced_channel.per_endpoint_channels().len());
+
+    let mut client = TesterClient::new(load_balanced_channel);
+    assert_eq!(
+        HashSet::from(["good_server".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}
+
+#[tokio::test]
+async fn grpc_precheck_skips_endpoints_not_serving_grpc() {
+    // Scenario:
+    // The DNS probe returns the IP of a plain HTTP server alongside a good server.
+    // With the gRPC precheck enabled only the good server is reported.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+
+    let http1_server = start_http1_server().await;
+    resolver
+        .add_ip_without_server("http1_server".into(), http1_server.to_string())
+        .await;
+    resolver
+        .add_server_with_provided_impl(
+            "good_server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "good_server".to_string(),
+            },
+        )
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .connect_timeout(Duration::from_secs(1))
+        .grpc_precheck(true)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let endpoints = load_balanced_channel.per_endpoint_channels();
+    assert_eq!(1, endpoints.len());
+    assert_ne!(http1_server, endpoints[0].0);
+
+    let mut client = TesterClient::new(load_balanced_channel);
+    assert_eq!(
+        HashSet::from(["good_server".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}
+
+#[tokio::test]
+async fn overall_timeout_bounds_attempt_with_longer_timeout_per_try() {
+    // Scenario:
+    // The DNS probe returns the IP of a server that accepts connections but never answers.
+    // The per-try timeout is longer than the overall request timeout, so the call must be
+    // cut short by the channel-level timeout and reported as a deadline exceeded.
+    let address = start_unresponsive_server().await;
+
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("unresponsive_server".into(), address.to_string())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .timeout(Duration::from_millis(200))
+        .timeout_per_try(Duration::from_secs(10))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let status = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.test(tonic::Request::new(Ping {})),
+    )
+    .await
+    .expect("took longer than the overall timeout to fail")
+    .expect_err("The call without a backing server should fail");
+
+    assert_eq!(tonic::Code::DeadlineExceeded, status.code());
+}
+
+#[tokio::test]
+async fn first_resolution_completes_after_first_successful_probe() {
+    // Scenario:
+    // The resolver fails until it is switched on. The channel is built lazily,
+    // so waiting for the first resolution only completes once the resolver succeeds.
+    #[derive(Clone, Default)]
+    struct SwitchedResolve {
+        on: Arc<std::sync::atomic::AtomicBool>,
+    }
+    #[async_trait::async_trait]
+    impl LookupService for SwitchedResolve {
+        async fn resolve_service_endpoints(
+            &self,
+            _definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+            if !self.on.load(std::sync::atomic::Ordering::SeqCst) {
+                anyhow::bail!("could not reach dns")
+            }
+            Ok(HashSet::new())
+        }
+    }
+
+    let resolver = SwitchedResolve::default();
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(3))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    tokio::time::timeout(
+        Duration::from_millis(50),
+        load_balanced_channel.first_resolution(),
+    )
+    .await
+    .expect_err("resolved while the resolver is failing");
+
+    resolver.on.store(true, std::sync::atomic::Ordering::SeqCst);
+
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        load_balanced_channel.first_resolution(),
+    )
+    .await
+    .expect("did not resolve once the resolver succeeds");
+}
+
+#[tokio::test]
+async fn ready_signal_completes_apart_from_the_channel() {
+    let lookup_service = ginepro::test_util::ScriptedLookupService::new([]);
+    lookup_service.set_failing();
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(lookup_service.clone())
+        .dns_probe_interval(Duration::from_millis(3))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let mut ready = tokio::spawn(load_balanced_channel.ready_signal());
+    tokio::time::timeout(Duration::from_millis(50), &mut ready)
+        .await
+        .expect_err("ready while the resolver is failing");
+
+    lookup_service.set_endpoints([]);
+    tokio::time::timeout(Duration::from_secs(1), ready)
+        .await
+        .expect("not ready once the resolver succeeds")
+        .unwrap();
+}
+
+struct EmptyResolve;
+#[async_trait::async_trait]
+impl LookupService for EmptyResolve {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        Ok(HashSet::new())
+    }
+}
+
+#[tokio::test]
+async fn builder_and_resolve_shall_succeed_when_no_ips_are_returned() {
+    assert!(LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(20),
+        })
+        .channel()
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn builder_and_resolve_shall_fail_when_no_ips_are_returned_and_required() {
+    let err = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .fail_on_empty_resolution(true)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(20),
+        })
+        .channel()
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        err.downcast_ref::<ginepro::ResolutionError>(),
+        Some(ginepro::ResolutionError::NoEndpoints)
+    ));
+}
+
+#[tokio::test]
+async fn global_concurrency_limit_rejects_requests_over_the_cap() {
+    // Scenario:
+    // The only endpoint never answers, so the first request stays in flight.
+    // With a global limit of one request, a second request is rejected immediately.
+    let address = start_unresponsive_server().await;
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("unresponsive_server".into(), address.to_string())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .global_concurrency_limit(1)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let mut in_flight_client = TesterClient::new(load_balanced_channel.clone());
+    let _in_flight =
+        tokio::spawn(async move { in_flight_client.test(tonic::Request::new(Ping {})).await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let status = tokio::time::timeout(
+        Duration::from_secs(1),
+        TesterClient::new(load_balanced_channel).test(tonic::Request::new(Ping {})),
+    )
+    .await
+    .expect("the request over the limit was not rejected immediately")
+    .expect_err("the request over the limit should fail");
+
+    assert_eq!(tonic::Code::ResourceExhausted, status.code());
+}
+
+/// Counts the requests going through the wrapped service.
+#[derive(Clone)]
+struct CountingLayer {
+    count: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl<S> tower_layer::Layer<S> for CountingLayer {
+    type Service = CountingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CountingService {
+            inner,
+            count: Arc::clone(&self.count),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CountingService<S> {
+    inner: S,
+    count: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl<S: tower_service::Service<R>, R> tower_service::Service<R> for CountingService<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.inner.call(request)
+    }
+}
+
+#[tokio::test]
+async fn channel_layer_wraps_every_request() {
+    // Scenario:
+    // A layer counting requests is set on the builder.
+    // Every request made through the load balanced channel goes through it.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+
+    let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .with_channel_layer(CountingLayer {
+            count: Arc::clone(&count),
+        })
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let mut client = TesterClient::new(load_balanced_channel);
+    let servers_called = call_servers(&mut client, &mut receiver, 3).await;
+
+    assert_eq!(HashSet::from(["server".to_string()]), servers_called);
+    assert_eq!(3, count.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+/// Resolves no endpoints, once released.
+struct GatedResolve {
+    release: Arc<tokio::sync::Notify>,
+}
+
+#[async_trait::async_trait]
+impl LookupService for GatedResolve {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        self.release.notified().await;
+        Ok(HashSet::new())
+    }
+}
+
+#[tokio::test]
+async fn is_probing_while_the_resolution_is_in_flight() {
+    let release = Arc::new(tokio::sync::Notify::new());
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(GatedResolve {
+            release: Arc::clone(&release),
+        })
+        .dns_probe_interval(Duration::from_secs(60))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !load_balanced_channel.is_probing() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the probe never started resolving");
+
+    release.notify_one();
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        load_balanced_channel.first_resolution(),
+    )
+    .await
+    .expect("did not resolve once released");
+
+    assert!(!load_balanced_channel.is_probing());
+}
+
+/// Never resolves, and flags when the resolution is dropped.
+struct HangingResolve {
+    cancelled: Arc<std::sync::atomic::AtomicBool>,
+}
+
+struct SetOnDrop(Arc<std::sync::atomic::AtomicBool>);
+
+impl Drop for SetOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[async_trait::async_trait]
+impl LookupService for HangingResolve {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let _cancelled = SetOnDrop(Arc::clone(&self.cancelled));
+        futures::future::pending().await
+    }
+}
+
+#[tokio::test]
+async fn dropping_the_channel_cancels_the_in_flight_resolution() {
+    let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(HangingResolve {
+            cancelled: Arc::clone(&cancelled),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !load_balanced_channel.is_probing() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the probe never started resolving");
+    assert!(!cancelled.load(std::sync::atomic::Ordering::SeqCst));
+
+    drop(load_balanced_channel);
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !cancelled.load(std::sync::atomic::Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the in-flight resolution was not cancelled");
+}
+
+#[tokio::test]
+async fn discover_yields_the_endpoint_changes() {
+    use futures::TryStreamExt;
+    use tower::discover::Change;
+
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".into(), "127.0.0.1:5000".into())
+        .await;
+    resolver
+        .add_ip_without_server("server_b".into(), "127.0.0.2:5000".into())
+        .await;
+
+    let mut discover = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .discover()
+        .await
+        .expect("failed to init");
+
+    let mut inserted = HashSet::new();
+    for _ in 0..2 {
+        match discover.try_next().await.unwrap() {
+            Some(Change::Insert(address, _)) => inserted.insert(address),
+            change => panic!("expected an insert, got {:?}", change),
+        };
+    }
+    assert_eq!(
+        HashSet::from([
+            "127.0.0.1:5000".parse().unwrap(),
+            "127.0.0.2:5000".parse().unwrap()
+        ]),
+        inserted
+    );
+
+    resolver.remove_ip_and_not_server("server_b".into()).await;
+
+    let change = tokio::time::timeout(Duration::from_secs(5), discover.try_next())
+        .await
+        .expect("the removal was not discovered")
+        .unwrap();
+    assert!(
+        matches!(change, Some(Change::Remove(address)) if address == "127.0.0.2:5000".parse().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn seed_endpoints_are_usable_before_the_first_probe() {
+    // Scenario:
+    // The resolver never answers, but the channel is seeded with the address of a server.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server".to_string(),
+            },
+        )
+        .await;
+    let seed = resolver
+        .resolve_service_endpoints(&ServiceDefinition::from_parts("test", 5000).unwrap())
+        .await
+        .unwrap();
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(GatedResolve {
+            release: Arc::new(tokio::sync::Notify::new()),
+        })
+        .seed_endpoints(seed)
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+    let servers_called = tokio::time::timeout(
+        Duration::from_secs(5),
+        call_servers(&mut client, &mut receiver, 1),
+    )
+    .await
+    .expect("the seed endpoint was not called");
+
+    assert_eq!(HashSet::from(["server".to_string()]), servers_called);
+    assert!(load_balanced_channel.is_probing());
+}
+
+/// Records the outcome of every resolution.
+#[derive(Default)]
+struct RecordingObserver {
+    resolutions: std::sync::Mutex<Vec<(Duration, bool)>>,
+    below_minimum: std::sync::Mutex<Vec<(usize, usize)>>,
+    first_resolutions: std::sync::Mutex<Vec<(Duration, usize)>>,
+    changes: std::sync::Mutex<Vec<(Vec<SocketAddr>, Vec<SocketAddr>)>>,
+    churns: std::sync::Mutex<Vec<ginepro::ProbeChurn>>,
+}
+
+impl ginepro::ProbeObserver for RecordingObserver {
+    fn on_resolution(&self, duration: Duration, succeeded: bool) {
+        self.resolutions.lock().unwrap().push((duration, succeeded));
+    }
+
+    fn on_endpoints_below_minimum(&self, endpoints: usize, minimum: usize) {
+        self.below_minimum
+            .lock()
+            .unwrap()
+            .push((endpoints, minimum));
+    }
+
+    fn on_first_resolution(&self, elapsed: Duration, endpoints: usize) {
+        self.first_resolutions
+            .lock()
+            .unwrap()
+            .push((elapsed, endpoints));
+    }
+
+    fn on_endpoints_changed(&self, added: &[SocketAddr], removed: &[SocketAddr]) {
+        self.changes
+            .lock()
+            .unwrap()
+            .push((added.to_vec(), removed.to_vec()));
+    }
+
+    fn on_probe_churn(&self, churn: ginepro::ProbeChurn) {
+        self.churns.lock().unwrap().push(churn);
+    }
+}
+
+#[tokio::test]
+async fn probe_observer_is_notified_of_the_resolution_duration() {
+    let observer = Arc::new(RecordingObserver::default());
+
+    LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .probe_observer(observer.clone())
+        .dns_probe_interval(Duration::from_secs(60))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let resolutions = observer.resolutions.lock().unwrap().clone();
+    assert!(!resolutions.is_empty());
+    assert!(resolutions
+        .iter()
+        .all(|(duration, succeeded)| { *succeeded && *duration < Duration::from_secs(5) }));
+}
+
+/// Fails the first `failures` resolutions, then resolves a single address.
+struct EventuallyResolve {
+    failures: usize,
+    attempts: std::sync::atomic::AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl LookupService for EventuallyResolve {
+    async fn resolve_service_endpoints(
+        &self,
+        _definition: &ServiceDefinition,
+    ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+        let attempt = self
+            .attempts
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        if attempt < self.failures {
+            anyhow::bail!("records not populated yet");
+        }
+        Ok(HashSet::from(["127.0.0.1:5000".parse().unwrap()]))
+    }
+}
+
+#[tokio::test]
+async fn eager_retry_backoff_retries_until_endpoints_are_resolved() {
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EventuallyResolve {
+            failures: 3,
+            attempts: Default::default(),
+        })
+        .eager_retry_backoff(Duration::from_millis(10))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    assert_eq!(1, load_balanced_channel.per_endpoint_channels().len());
+}
+
+#[tokio::test]
+async fn eager_retry_backoff_fails_once_the_timeout_elapses() {
+    LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EventuallyResolve {
+            failures: usize::MAX,
+            attempts: Default::default(),
+        })
+        .eager_retry_backoff(Duration::from_millis(10))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_millis(200),
+        })
+        .channel()
+        .await
+        .expect_err("the resolution never succeeds");
+}
+
+/// Wait until `condition` holds, failing the test after a few seconds.
+async fn wait_until(condition: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the condition was never met");
+}
+
+#[tokio::test]
+async fn endpoints_callbacks_fire_on_transitions() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let available = Arc::new(AtomicUsize::new(0));
+    let exhausted = Arc::new(AtomicUsize::new(0));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".into(), "127.0.0.1:5000".into())
+        .await;
+
+    let _load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .on_endpoints_available({
+            let available = Arc::clone(&available);
+            move || {
+                available.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .on_endpoints_exhausted({
+            let exhausted = Arc::clone(&exhausted);
+            move || {
+                exhausted.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| available.load(Ordering::SeqCst) == 1).await;
+
+    // Adding an endpoint to a non-empty set is not a transition.
+    resolver
+        .add_ip_without_server("server_b".into(), "127.0.0.2:5000".into())
+        .await;
+    resolver.remove_ip_and_not_server("server_a".into()).await;
+    resolver.remove_ip_and_not_server("server_b".into()).await;
+    wait_until(|| exhausted.load(Ordering::SeqCst) == 1).await;
+
+    resolver
+        .add_ip_without_server("server_a".into(), "127.0.0.1:5000".into())
+        .await;
+    wait_until(|| available.load(Ordering::SeqCst) == 2).await;
+    assert_eq!(1, exhausted.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn snapshot_reflects_the_outcome_of_the_last_probe() {
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EventuallyResolve {
+            failures: 1,
+            attempts: Default::default(),
+        })
+        .dns_probe_interval(Duration::from_millis(100))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| load_balanced_channel.snapshot().last_error.is_some()).await;
+    let snapshot = load_balanced_channel.snapshot();
+    let last_error = snapshot.last_error.unwrap();
+    assert!(last_error.contains("test.com:5000"), "{}", last_error);
+    assert!(
+        last_error.contains("records not populated yet"),
+        "{}",
+        last_error
+    );
+    assert!(snapshot.endpoints.is_empty());
+    assert_eq!(0, snapshot.generation);
+
+    wait_until(|| !load_balanced_channel.snapshot().endpoints.is_empty()).await;
+    let snapshot = load_balanced_channel.snapshot();
+    assert_eq!(
+        vec!["127.0.0.1:5000".parse::<SocketAddr>().unwrap()],
+        snapshot.endpoints
+    );
+    assert_eq!(None, snapshot.last_error);
+    assert!(snapshot.last_probe.is_some());
+    assert_eq!(1, snapshot.generation);
+    assert_eq!(
+        load_balanced_channel.service_definitions(),
+        snapshot.service_definitions
+    );
+}
+
+#[tokio::test]
+async fn overall_timeout_bounds_requests_with_a_longer_endpoint_timeout() {
+    let address = start_unresponsive_server().await;
+
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("unresponsive_server".into(), address.to_string())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .timeout(Duration::from_secs(10))
+        .overall_timeout(Duration::from_millis(200))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let status = tokio::time::timeout(
+        Duration::from_secs(2),
+        client.test(tonic::Request::new(Ping {})),
+    )
+    .await
+    .expect("took longer than the overall timeout to fail")
+    .expect_err("The call without a backing server should fail");
+
+    assert_eq!(tonic::Code::DeadlineExceeded, status.code());
+}
+
+#[tokio::test(start_paused = true)]
+async fn probe_cycles_follow_the_paused_clock() {
+    // Scenario:
+    // The probe only waits on the tokio clock, so with the clock paused a probe cycle
+    // only happens when the test advances time, instantly and deterministically.
+    let probe_interval = Duration::from_secs(60);
+    let observer = Arc::new(RecordingObserver::default());
+
+    let _load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .probe_observer(observer.clone())
+        .dns_probe_interval(probe_interval)
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let resolutions = || observer.resolutions.lock().unwrap().len();
+    // Let the spawned probe run until it waits on the clock again.
+    let settle = || async {
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+        }
+    };
+
+    let start = std::time::Instant::now();
+    settle().await;
+    assert_eq!(1, resolutions());
+
+    for cycle in 2..=5 {
+        tokio::time::advance(probe_interval - Duration::from_millis(1)).await;
+        settle().await;
+        assert_eq!(cycle - 1, resolutions());
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        settle().await;
+        assert_eq!(cycle, resolutions());
+    }
+
+    // Four minutes of probing went by without waiting for them.
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn pinned_endpoint_receives_every_request() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(20);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    for name in ["server_a", "server_b"] {
+        resolver
+            .add_server_with_provided_impl(
+                name.to_string(),
+                TesterImpl {
+                    sender: Arc::clone(&sender),
+                    name: name.to_string(),
+                },
+            )
+            .await;
+    }
+    let address_b: SocketAddr = resolver.ips.read().await["server_b"].parse().unwrap();
+    let count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .with_channel_layer(CountingLayer {
+            count: Arc::clone(&count),
+        })
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+
+    load_balanced_channel
+        .pin_endpoint(Some("127.0.0.1:1".parse().unwrap()))
+        .expect_err("the address is not one of the endpoints");
+
+    // The pinned endpoint receives every request, through the channel layer.
+    load_balanced_channel.pin_endpoint(Some(address_b)).unwrap();
+    assert_eq!(
+        HashSet::from(["server_b".to_string()]),
+        call_servers(&mut client, &mut receiver, 10).await
+    );
+    assert_eq!(10, count.load(std::sync::atomic::Ordering::SeqCst));
+
+    load_balanced_channel.pin_endpoint(None).unwrap();
+    assert_eq!(
+        HashSet::from(["server_a".to_string(), "server_b".to_string()]),
+        call_servers(&mut client, &mut receiver, 20).await
+    );
+
+    // Requests fail once the pinned endpoint is no longer discovered.
+    load_balanced_channel.pin_endpoint(Some(address_b)).unwrap();
+    resolver.remove_server("server_b".to_string()).await;
+    wait_until(|| {
+        !load_balanced_channel
+            .snapshot()
+            .endpoints
+            .contains(&address_b)
+    })
+    .await;
+    let status = client
+        .test(tonic::Request::new(Ping {}))
+        .await
+        .expect_err("the pinned endpoint is gone");
+    assert_eq!(tonic::Code::Unavailable, status.code());
+}
+
+#[tokio::test]
+async fn calls_are_spread_fairly_across_endpoints() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let sender = Arc::new(Mutex::new(sender));
+    // The histogram identifies the servers from the responses.
+    tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+    let mut resolver = TestDnsResolver::default();
+    for name in ["server_a", "server_b", "server_c"] {
+        resolver
+            .add_server_with_provided_impl(
+                name.to_string(),
+                TesterImpl {
+                    sender: Arc::clone(&sender),
+                    name: name.to_string(),
+                },
+            )
+            .await;
+    }
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let histogram =
+        ginepro::test_util::call_histogram(&load_balanced_channel, 300, |channel| async move {
+            let response = TesterClient::new(channel)
+                .test(tonic::Request::new(Ping {}))
+                .await?;
+            Ok::<_, tonic::Status>(get_payload_raw(
+                response.into_inner().payload.expect("no payload"),
+            ))
+        })
+        .await
+        .expect("failed to call server");
+
+    assert_eq!(300, histogram.calls());
+    histogram.assert_fair(3, 0.5);
+}
+
+#[tokio::test]
+async fn resolved_addresses_are_rewritten_before_connecting() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+    // The resolved address is not the one the server listens on.
+    let server_address: SocketAddr = std::mem::replace(
+        resolver.ips.write().await.get_mut("server_a").unwrap(),
+        "10.0.0.1:1".to_string(),
+    )
+    .parse()
+    .unwrap();
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver)
+        .dns_probe_interval(Duration::from_millis(10))
+        .address_rewrite(move |address| match address {
+            address if address == "10.0.0.1:1".parse().unwrap() => server_address,
+            address => address,
+        })
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+
+    assert_eq!(
+        vec![server_address],
+        load_balanced_channel.snapshot().endpoints
+    );
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}
+
+#[tokio::test]
+async fn panicking_lookup_services_are_retried() {
+    struct PanicOnce {
+        attempts: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LookupService for PanicOnce {
+        async fn resolve_service_endpoints(
+            &self,
+            _definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+            if self
+                .attempts
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+                == 0
+            {
+                panic!("resolver bug");
+            }
+            Ok(HashSet::from(["127.0.0.1:5000".parse().unwrap()]))
+        }
+    }
+
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(PanicOnce {
+            attempts: Default::default(),
+        })
+        .dns_probe_interval(Duration::from_millis(100))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| load_balanced_channel.snapshot().last_error.is_some()).await;
+    let last_error = load_balanced_channel.snapshot().last_error.unwrap();
+    assert!(last_error.contains("resolver bug"), "{}", last_error);
+
+    wait_until(|| !load_balanced_channel.snapshot().endpoints.is_empty()).await;
+    assert_eq!(None, load_balanced_channel.snapshot().last_error);
+}
+
+#[tokio::test]
+async fn compression_is_negotiated_by_the_client() {
+    use tonic::codec::CompressionEncoding;
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let server = TestServer::start(
+        TesterServer::new(TesterImpl {
+            sender: Arc::new(Mutex::new(sender)),
+            name: "server_a".to_string(),
+        })
+        .accept_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Gzip),
+        None,
+        None,
+    )
+    .await;
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".to_string(), server.address().to_string())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    // A server that does not accept gzip would reject the compressed requests.
+    let mut client = TesterClient::new(load_balanced_channel)
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}
+
+#[tokio::test]
+async fn eager_connect_requires_enough_endpoints_to_accept_a_connection() {
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+    resolver
+        .add_ip_without_server("server_b".to_string(), "127.0.0.1:1".to_string())
+        .await;
+    let builder = |min_ready| {
+        LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+            .lookup_service(resolver.clone())
+            .connect_timeout(Duration::from_millis(500))
+            .resolution_strategy(ginepro::ResolutionStrategy::EagerConnect {
+                timeout: Duration::from_secs(5),
+                min_ready,
+            })
+            .channel()
+    };
+
+    let load_balanced_channel = builder(1).await.expect("failed to init");
+    assert_eq!(Some(1), load_balanced_channel.ready_at_startup());
+
+    let err = builder(2)
+        .await
+        .expect_err("only one endpoint accepts connections");
+    assert!(matches!(
+        err.downcast_ref::<ginepro::ResolutionError>(),
+        Some(ginepro::ResolutionError::NotEnoughReadyEndpoints {
+            ready: 1,
+            required: 2
+        })
+    ));
+}
+
+#[tokio::test]
+async fn channels_can_be_built_over_a_fixed_list_of_endpoints() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    for name in ["server_a", "server_b"] {
+        resolver
+            .add_server_with_provided_impl(
+                name.to_string(),
+                TesterImpl {
+                    sender: Arc::clone(&sender),
+                    name: name.to_string(),
+                },
+            )
+            .await;
+    }
+    let endpoints: Vec<SocketAddr> = resolver
+        .ips
+        .read()
+        .await
+        .values()
+        .map(|address| address.parse().unwrap())
+        .collect();
+
+    let load_balanced_channel = LoadBalancedChannel::from_endpoints(endpoints)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string(), "server_b".to_string()]),
+        call_servers(&mut client, &mut receiver, 20).await
+    );
+}
+
+#[tokio::test]
+async fn min_healthy_warn_fires_as_the_endpoints_drop_below_the_minimum() {
+    let observer = Arc::new(RecordingObserver::default());
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".to_string(), "127.0.0.1:5001".to_string())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(resolver.clone())
+        .probe_observer(observer.clone())
+        .min_healthy_warn(2)
+        .dns_probe_interval(Duration::from_millis(10))
+        .channel()
+        .await
+        .expect("failed to init");
+    let below_minimum = || observer.below_minimum.lock().unwrap().clone();
+
+    // The first probe finds too few endpoints.
+    wait_until(|| !below_minimum().is_empty()).await;
+    assert_eq!(vec![(1, 2)], below_minimum());
+
+    for (name, address) in [
+        ("server_b", "127.0.0.1:5002"),
+        ("server_c", "127.0.0.1:5003"),
+    ] {
+        resolver
+            .add_ip_without_server(name.to_string(), address.to_string())
+            .await;
+    }
+    wait_until(|| load_balanced_channel.snapshot().endpoints.len() == 3).await;
+    assert_eq!(vec![(1, 2)], below_minimum());
+
+    // Losing a single endpoint keeps the channel at the minimum.
+    resolver
+        .remove_ip_and_not_server("server_c".to_string())
+        .await;
+    wait_until(|| load_balanced_channel.snapshot().endpoints.len() == 2).await;
+    assert_eq!(vec![(1, 2)], below_minimum());
+
+    resolver
+        .remove_ip_and_not_server("server_b".to_string())
+        .await;
+    wait_until(|| below_minimum().len() == 2).await;
+    assert_eq!(vec![(1, 2), (1, 2)], below_minimum());
+}
+
+#[tokio::test]
+async fn tls_domain_name_is_left_untouched_when_not_overridden() {
+    use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+
+    let certificate = tests::tls::TestSslCertificate::generate();
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let mut resolver = TestDnsResolver::new_with_tls(ServerTlsConfig::new().identity(
+        Identity::from_pem(certificate.pem_certificate(), certificate.pem_private_key()),
+    ));
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+    let address: SocketAddr = resolver.ips.read().await["server_a"].parse().unwrap();
+    // The certificate is issued for `localhost`, not for the placeholder hostname
+    // of a channel over a fixed list of endpoints.
+    let tls_config = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(certificate.pem_certificate()))
+        .domain_name("localhost");
+    let channel = |override_tls_domain_name| {
+        LoadBalancedChannel::from_endpoints([address])
+            .with_tls(tls_config.clone())
+            .override_tls_domain_name(override_tls_domain_name)
+            .timeout(Duration::from_secs(1))
+            .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+                timeout: Duration::from_secs(5),
+            })
+            .channel()
+    };
+
+    let mut client = TesterClient::new(channel(true).await.expect("failed to init"));
+    client
+        .test(tonic::Request::new(Ping {}))
+        .await
+        .expect_err("the certificate is verified against the placeholder hostname");
+
+    let mut client = TesterClient::new(channel(false).await.expect("failed to init"));
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}
+
+#[tokio::test]
+async fn open_connections_are_counted_and_capped() {
+    let endpoints: Vec<SocketAddr> = (5001..5004)
+        .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+        .collect();
+    let load_balanced_channel = LoadBalancedChannel::from_endpoints(endpoints.clone())
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    assert_eq!(3, load_balanced_channel.open_connection_count());
+    // Every dedicated channel holds a connection of its own.
+    load_balanced_channel.per_endpoint_channels();
+    assert_eq!(6, load_balanced_channel.open_connection_count());
+
+    let load_balanced_channel = LoadBalancedChannel::from_endpoints(endpoints)
+        .max_open_connections(2)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    assert_eq!(2, load_balanced_channel.snapshot().endpoints.len());
+    assert_eq!(2, load_balanced_channel.open_connection_count());
+}
+
+#[tokio::test]
+async fn first_resolution_is_observed_once() {
+    let observer = Arc::new(RecordingObserver::default());
+
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EventuallyResolve {
+            failures: 2,
+            attempts: Default::default(),
+        })
+        .probe_observer(observer.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    load_balanced_channel.first_resolution().await;
+    // Let another probe succeed.
+    let last_probe = load_balanced_channel.snapshot().last_probe;
+    wait_until(|| load_balanced_channel.snapshot().last_probe != last_probe).await;
+
+    let first_resolutions = observer.first_resolutions.lock().unwrap().clone();
+    assert_eq!(1, first_resolutions.len());
+    let (elapsed, endpoints) = first_resolutions[0];
+    assert_eq!(1, endpoints);
+    // The probes that failed before are part of the wait.
+    assert!(elapsed >= Duration::from_millis(20), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn warmup_concurrency_still_connects_every_endpoint() {
+    let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    for name in ["server_a", "server_b", "server_c"] {
+        resolver
+            .add_server_with_provided_impl(
+                name.to_string(),
+                TesterImpl {
+                    sender: Arc::clone(&sender),
+                    name: name.to_string(),
+                },
+            )
+            .await;
+    }
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver)
+        .warmup_concurrency(1)
+        .resolution_strategy(ginepro::ResolutionStrategy::EagerConnect {
+            timeout: Duration::from_secs(5),
+            min_ready: 3,
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    assert_eq!(Some(3), load_balanced_channel.ready_at_startup());
+}
+
+#[tokio::test]
+async fn tls_config_can_be_read_from_files() {
+    use tonic::transport::{Identity, ServerTlsConfig};
+
+    let certificate = tests::tls::TestSslCertificate::generate();
+    let directory = std::env::temp_dir().join(format!("ginepro-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let ca_path = directory.join("ca.pem");
+    std::fs::write(&ca_path, certificate.pem_certificate()).unwrap();
+    let malformed_path = directory.join("malformed.pem");
+    std::fs::write(&malformed_path, "not a certificate").unwrap();
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let mut resolver = TestDnsResolver::new_with_tls(ServerTlsConfig::new().identity(
+        Identity::from_pem(certificate.pem_certificate(), certificate.pem_private_key()),
+    ));
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+
+    let err = LoadBalancedChannel::builder(("localhost", 5000))
+        .with_tls_from_files(&malformed_path, None)
+        .err()
+        .expect("the file does not hold a certificate");
+    assert!(
+        format!("{:#}", err).contains("does not hold any PEM certificate"),
+        "{:#}",
+        err
+    );
+
+    let load_balanced_channel = LoadBalancedChannel::builder(("localhost", 5000))
+        .with_tls_from_files(&ca_path, None)
+        .expect("failed to read the tls files")
+        .lookup_service(resolver)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn refresh_hints_override_the_probe_interval() {
+    struct HintingResolve {
+        resolutions: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl LookupService for HintingResolve {
+        async fn resolve_service_endpoints(
+            &self,
+            _definition: &ServiceDefinition,
+        ) -> Result<HashSet<SocketAddr>, anyhow::Error> {
+            unreachable!("the probe resolves with metadata")
+        }
+
+        async fn resolve_service_endpoints_with_metadata(
+            &self,
+            _definition: &ServiceDefinition,
+        ) -> Result<ginepro::Resolution, anyhow::Error> {
+            self.resolutions
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(
+                ginepro::Resolution::new(HashSet::from(["127.0.0.1:5000".parse().unwrap()]))
+                    .with_refresh_after(Duration::from_millis(10)),
+            )
+        }
+    }
+
+    let resolutions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let _load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(HintingResolve {
+            resolutions: Arc::clone(&resolutions),
+        })
+        .dns_probe_interval(Duration::from_secs(3600))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| resolutions.load(std::sync::atomic::Ordering::SeqCst) >= 3).await;
+}
+
+#[tokio::test]
+async fn resolving_once_stops_probing() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .resolution_strategy(ginepro::ResolutionStrategy::Once {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+
+    resolver
+        .add_server_with_provided_impl(
+            "server_b".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server_b".to_string(),
+            },
+        )
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(1, load_balanced_channel.snapshot().endpoints.len());
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 10).await
+    );
+}
+
+#[tokio::test]
+async fn zero_downtime_cutover_waits_for_the_new_endpoints() {
+    let observer = Arc::new(RecordingObserver::default());
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+    let server_a: SocketAddr = resolver.ips.read().await["server_a"].parse().unwrap();
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .connect_timeout(Duration::from_millis(100))
+        .zero_downtime_cutover(true)
+        .probe_observer(observer.clone())
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+
+    // Replace the endpoint with one that nothing listens on.
+    resolver
+        .remove_ip_and_not_server("server_a".to_string())
+        .await;
+    resolver
+        .add_ip_without_server("unready".to_string(), "127.0.0.1:1".to_string())
+        .await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(load_balanced_channel
+        .snapshot()
+        .endpoints
+        .contains(&server_a));
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+
+    // Replace it with one that is ready.
+    resolver
+        .remove_ip_and_not_server("unready".to_string())
+        .await;
+    resolver
+        .add_server_with_provided_impl(
+            "server_b".to_string(),
+            TesterImpl {
+                sender: Arc::clone(&sender),
+                name: "server_b".to_string(),
+            },
+        )
+        .await;
+    let server_b: SocketAddr = resolver.ips.read().await["server_b"].parse().unwrap();
+
+    wait_until(|| load_balanced_channel.snapshot().endpoints == vec![server_b]).await;
+    assert_eq!(
+        HashSet::from(["server_b".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+
+    // The cutover is observed as a single probe replacing the endpoint.
+    assert!(observer
+        .churns
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|churn| churn.added == 1 && churn.removed == 1 && churn.stable == 0));
+    assert!(observer
+        .changes
+        .lock()
+        .unwrap()
+        .contains(&(vec![server_b], vec![])));
+}
+
+#[tokio::test]
+async fn effective_settings_are_exposed_by_the_channel() {
+    let defaults = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .channel()
+        .await
+        .expect("failed to init");
+
+    assert_eq!(Duration::from_secs(10), defaults.probe_interval());
+    assert_eq!(None, defaults.timeout());
+    assert_eq!(None, defaults.connect_timeout());
+    assert!(!defaults.is_tls_enabled());
+
+    let configured = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .dns_probe_interval(Duration::from_secs(1))
+        .timeout(Duration::from_millis(500))
+        .timeout_per_try(Duration::from_millis(200))
+        .channel()
+        .await
+        .expect("failed to init");
+
+    assert_eq!(Duration::from_secs(1), configured.probe_interval());
+    assert_eq!(Some(Duration::from_millis(500)), configured.timeout());
+    // The connection timeout defaults to the timeout of a single try.
+    assert_eq!(
+        Some(Duration::from_millis(200)),
+        configured.connect_timeout()
+    );
+}
+
+#[tokio::test]
+async fn consecutive_failures_callback_fires_every_n_failures() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let fired = Arc::new(AtomicUsize::new(0));
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EventuallyResolve {
+            failures: 5,
+            attempts: Default::default(),
+        })
+        .dns_probe_interval(Duration::from_millis(10))
+        .on_consecutive_failures(2, {
+            let fired = Arc::clone(&fired);
+            move || {
+                fired.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| !load_balanced_channel.snapshot().endpoints.is_empty()).await;
+    // Fired after the second and the fourth failures, not after the fifth.
+    assert_eq!(2, fired.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn failures_are_still_recorded_without_logging_them() {
+    let observer = Arc::new(RecordingObserver::default());
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EventuallyResolve {
+            failures: usize::MAX,
+            attempts: Default::default(),
+        })
+        .dns_probe_interval(Duration::from_millis(10))
+        .log_probe_errors(false)
+        .probe_observer(observer.clone())
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| load_balanced_channel.snapshot().last_error.is_some()).await;
+    assert!(observer
+        .resolutions
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(_, succeeded)| !succeeded));
+}
+
+#[tokio::test]
+async fn probe_observer_receives_the_added_and_removed_addresses() {
+    let observer = Arc::new(RecordingObserver::default());
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".into(), "127.0.0.1:5001".into())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .probe_observer(observer.clone())
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    resolver
+        .remove_ip_and_not_server("server_a".to_string())
+        .await;
+    resolver
+        .add_ip_without_server("server_b".into(), "127.0.0.1:5002".into())
+        .await;
+    let server_b: SocketAddr = "127.0.0.1:5002".parse().unwrap();
+    wait_until(|| load_balanced_channel.snapshot().endpoints == vec![server_b]).await;
+
+    let server_a: SocketAddr = "127.0.0.1:5001".parse().unwrap();
+    assert_eq!(
+        vec![(vec![server_a], vec![]), (vec![server_b], vec![server_a])],
+        *observer.changes.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn resolutions_beyond_the_hard_limit_are_rejected() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_ip_without_server("server_a".into(), "127.0.0.1:5001".into())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .max_endpoints_hard_limit(2)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    // Both are added at once, so that no probe sees only one of them.
+    resolver.ips.write().await.extend([
+        ("server_b".to_string(), "127.0.0.1:5002".to_string()),
+        ("server_c".to_string(), "127.0.0.1:5003".to_string()),
+    ]);
+    wait_until(|| load_balanced_channel.snapshot().last_error.is_some()).await;
+
+    let snapshot = load_balanced_channel.snapshot();
+    assert!(
+        snapshot.last_error.as_ref().unwrap().contains("limit of 2"),
+        "{:?}",
+        snapshot.last_error
+    );
+    assert_eq!(
+        vec!["127.0.0.1:5001".parse::<SocketAddr>().unwrap()],
+        snapshot.endpoints
+    );
+}
+
+#[tokio::test]
+async fn backends_behind_a_gateway_are_verified_against_its_domain_name() {
+    use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+
+    // The certificate of every backend is issued for `localhost`, the name of the gateway.
+    let certificate = tests::tls::TestSslCertificate::generate();
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let mut resolver = TestDnsResolver::new_with_tls(ServerTlsConfig::new().identity(
+        Identity::from_pem(certificate.pem_certificate(), certificate.pem_private_key()),
+    ));
+    resolver
+        .add_server_with_provided_impl(
+            "server_a".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "server_a".to_string(),
+            },
+        )
+        .await;
+    let origin: tonic::codegen::http::Uri = "https://localhost".parse().unwrap();
+
+    let load_balanced_channel =
+        LoadBalancedChannelBuilder::new_with_service(("backends.internal", 5000))
+            .lookup_service(resolver)
+            .with_tls(
+                ClientTlsConfig::new()
+                    .ca_certificate(Certificate::from_pem(certificate.pem_certificate())),
+            )
+            .tls_domain_name("localhost")
+            .try_with_endpoint_layer(move |endpoint| {
+                Ok::<_, std::convert::Infallible>(endpoint.origin(origin.clone()))
+            })
+            .timeout(Duration::from_secs(1))
+            .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+                timeout: Duration::from_secs(5),
+            })
+            .channel()
+            .await
+            .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}
+
+#[tokio::test]
+async fn channels_expose_the_service_definition_they_are_constructed_with() {
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(EmptyResolve)
+        .channel()
+        .await
+        .expect("failed to init");
+    let definition = ServiceDefinition::from_parts("test.com", 5000).unwrap();
+
+    load_balanced_channel
+        .add_service_definition(("other.com", 5000))
+        .unwrap();
+    assert_eq!(&definition, load_balanced_channel.service_definition());
+
+    let channels = HashMap::from([(
+        load_balanced_channel.service_definition().clone(),
+        load_balanced_channel.clone(),
+    )]);
+    assert!(channels.contains_key(&definition));
+}
+
+/// A server that is draining, failing every call with `UNAVAILABLE`.
+struct DrainingServer;
+
+#[async_trait::async_trait]
+impl shared_proto::pb::tester_server::Tester for DrainingServer {
+    async fn test(
+        &self,
+        _request: tonic::Request<Ping>,
+    ) -> Result<tonic::Response<shared_proto::pb::Pong>, tonic::Status> {
+        Err(tonic::Status::unavailable("shutting down"))
+    }
+}
+
+#[tokio::test]
+async fn unavailable_responses_trigger_an_early_probe() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl("draining".to_string(), DrainingServer)
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test.com", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_secs(60))
+        .reprobe_on_unavailable(true)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let status = client
+        .test(tonic::Request::new(Ping {}))
+        .await
+        .expect_err("the draining server fails every call");
+    assert_eq!(status.code(), tonic::Code::Unavailable);
+
+    // The draining server is replaced, while the next scheduled probe is a minute away.
+    resolver
+        .remove_ip_and_not_server("draining".to_string())
+        .await;
+    resolver
+        .add_server_with_provided_impl(
+            "healthy".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "healthy".to_string(),
+            },
+        )
+        .await;
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while client.test(tonic::Request::new(Ping {})).await.is_err() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("the draining server was never replaced");
+    assert_eq!(receiver.recv().await.as_deref(), Some("healthy"));
+}
+
+#[tokio::test]
+async fn removal_confirmations_ride_out_flapping_resolutions() {
+    use ginepro::test_util::{calls_during, FlapScript, ScriptedLookupService};
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+    let server = TestServer::start(
+        TesterServer::new(TesterImpl {
+            sender: Arc::new(Mutex::new(sender)),
+            name: "flapping".to_string(),
+        }),
+        None,
+        None,
+    )
+    .await;
+    let endpoints = [server.address().parse::<SocketAddr>().unwrap()];
+
+    let lookup_service = ScriptedLookupService::new(endpoints);
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test.com", 5000))
+        .lookup_service(lookup_service.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .removal_confirmations(20)
+        // Without endpoints calls wait for one, rather than fail.
+        .overall_timeout(Duration::from_millis(100))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    // The endpoint goes missing for a handful of probes, fewer than the confirmations.
+    let script = FlapScript::new()
+        .empty(Duration::from_millis(50))
+        .fail(Duration::from_millis(50))
+        .resolve(endpoints, Duration::from_millis(50))
+        .empty(Duration::from_millis(50))
+        .resolve(endpoints, Duration::from_millis(50));
+    let outcomes = calls_during(
+        &load_balanced_channel,
+        lookup_service.play(script),
+        Duration::from_millis(5),
+        |channel| async move { TesterClient::new(channel).test(Ping {}).await },
+    )
+    .await;
+
+    outcomes.assert_no_failures();
+    assert!(lookup_service.resolutions() > 5);
+}
+
+/// A server answering with the value of the `x-route` header of the request.
+struct RouteEcho;
+
+#[async_trait::async_trait]
+impl shared_proto::pb::tester_server::Tester for RouteEcho {
+    async fn test(
+        &self,
+        request: tonic::Request<Ping>,
+    ) -> Result<tonic::Response<shared_proto::pb::Pong>, tonic::Status> {
+        let route = request
+            .metadata()
+            .get("x-route")
+            .and_then(|route| route.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        Ok(tonic::Response::new(shared_proto::pb::Pong {
+            payload: Some(Payload::Raw(route)),
+        }))
+    }
+}
+
+#[tokio::test]
+async fn default_headers_are_added_unless_the_request_sets_them() {
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl("echo".to_string(), RouteEcho)
+        .await;
+
+    let mut headers = tonic::codegen::http::HeaderMap::new();
+    headers.insert(
+        "x-route",
+        tonic::codegen::http::HeaderValue::from_static("gateway"),
+    );
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test.com", 5000))
+        .lookup_service(resolver)
+        .default_headers(headers)
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    let pong = client.test(tonic::Request::new(Ping {})).await.unwrap();
+    assert_eq!(
+        "gateway",
+        get_payload_raw(pong.into_inner().payload.unwrap())
+    );
+
+    let mut request = tonic::Request::new(Ping {});
+    request
+        .metadata_mut()
+        .insert("x-route", "override".parse().unwrap());
+    let pong = client.test(request).await.unwrap();
+    assert_eq!(
+        "override",
+        get_payload_raw(pong.into_inner().payload.unwrap())
+    );
+}
+
+#[tokio::test]
+async fn endpoints_failing_connection_checks_are_ejected() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let mut resolver = TestDnsResolver::default();
+    resolver
+        .add_server_with_provided_impl(
+            "grpc".to_string(),
+            TesterImpl {
+                sender: Arc::new(Mutex::new(sender)),
+                name: "grpc".to_string(),
+            },
+        )
+        .await;
+    resolver
+        .add_ip_without_server("http1".to_string(), start_http1_server().await.to_string())
+        .await;
+
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("test.com", 5000))
+        .lookup_service(resolver.clone())
+        .dns_probe_interval(Duration::from_millis(10))
+        .eject_unconnectable_after(2)
+        .channel()
+        .await
+        .expect("failed to init");
+
+    wait_until(|| load_balanced_channel.snapshot().endpoints.len() == 1).await;
+    let grpc: SocketAddr = resolver.ips.read().await["grpc"].parse().unwrap();
+    assert_eq!(vec![grpc], load_balanced_channel.snapshot().endpoints);
+
+    let mut client = TesterClient::new(load_balanced_channel);
+    assert_eq!(
+        HashSet::from(["grpc".to_string()]),
+        call_servers(&mut client, &mut receiver, 10).await
+    );
+}
+
+#[tokio::test]
+async fn method_router_routes_by_the_longest_method_prefix() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let sender = Arc::new(Mutex::new(sender));
+    let mut pools = Vec::new();
+    for pool in ["reads", "writes"] {
+        let mut resolver = TestDnsResolver::default();
+        resolver
+            .add_server_with_provided_impl(
+                pool.to_string(),
+                TesterImpl {
+                    sender: Arc::clone(&sender),
+                    name: pool.to_string(),
+                },
+            )
+            .await;
+        let channel = LoadBalancedChannelBuilder::new_with_service((pool, 5000))
+            .lookup_service(resolver.clone())
+            .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+                timeout: Duration::from_secs(5),
+            })
+            .channel()
+            .await
+            .expect("failed to init");
+        // Keep the servers up until the end of the test.
+        pools.push((resolver, channel));
+    }
+    let (reads, writes) = (pools[0].1.clone(), pools[1].1.clone());
+
+    let router = ginepro::MethodRouter::new(reads.clone())
+        .route("/test.", reads.clone())
+        .route("/test.Tester/", writes.clone());
+    TesterClient::new(router)
+        .test(Ping {})
+        .await
+        .expect("failed to call server");
+    assert_eq!(Some("writes".to_string()), receiver.recv().await);
+
+    let router = ginepro::MethodRouter::new(reads).route("/test.Other/", writes);
+    TesterClient::new(router)
+        .test(Ping {})
+        .await
+        .expect("failed to call server");
+    assert_eq!(Some("reads".to_string()), receiver.recv().await);
+}
+
+#[tokio::test]
+async fn probe_observer_receives_the_churn_of_every_probe() {
+    let observer = Arc::new(RecordingObserver::default());
+    let (a, b) = (
+        SocketAddr::from(([127, 0, 0, 1], 5001)),
+        SocketAddr::from(([127, 0, 0, 1], 5002)),
+    );
+    let lookup_service = ginepro::test_util::ScriptedLookupService::new([a, b]);
+
+    let _load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(lookup_service.clone())
+        .probe_observer(observer.clone())
+        .dns_probe_interval(Duration::from_millis(5))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let churn = |index: usize| observer.churns.lock().unwrap().get(index).copied();
+    let initial = churn(0).expect("the eager resolution is a probe");
+    assert_eq!(
+        (0, 2, 0, 0),
+        (
+            initial.stable,
+            initial.added,
+            initial.removed,
+            initial.readded
+        )
+    );
+
+    lookup_service.set_endpoints([a]);
+    wait_until(|| {
+        observer
+            .churns
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|churn| churn.removed == 1)
+    })
+    .await;
+    lookup_service.set_endpoints([a, b]);
+    wait_until(|| {
+        observer
+            .churns
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|churn| churn.readded == 1)
+    })
+    .await;
+
+    let churns = observer.churns.lock().unwrap().clone();
+    let readded = churns.iter().find(|churn| churn.readded == 1).unwrap();
+    assert_eq!((1, 1, 0), (readded.stable, readded.added, readded.removed));
+    // The probes in between changed nothing.
+    assert!(churns
+        .iter()
+        .filter(|churn| churn.added == 0 && churn.removed == 0)
+        .all(|churn| churn.stable > 0));
+}
+
+#[tokio::test]
+async fn changes_below_the_report_threshold_are_deferred() {
+    let addresses: Vec<SocketAddr> = (5001..5006)
+        .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+        .collect();
+    let lookup_service = ginepro::test_util::ScriptedLookupService::new(addresses[..3].to_vec());
+
+    let load_balanced_channel = LoadBalancedChannel::builder(("test.com", 5000))
+        .lookup_service(lookup_service.clone())
+        .dns_probe_interval(Duration::from_millis(5))
+        .change_report_threshold(ginepro::ChangeReportThreshold::Count(2))
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    assert_eq!(3, load_balanced_channel.snapshot().endpoints.len());
+
+    // A single new endpoint stays below the threshold.
+    lookup_service.set_endpoints(addresses[..4].to_vec());
+    let resolutions = lookup_service.resolutions();
+    wait_until(|| lookup_service.resolutions() > resolutions + 3).await;
+    assert_eq!(3, load_balanced_channel.snapshot().endpoints.len());
+
+    // The second one adds up to the threshold.
+    lookup_service.set_endpoints(addresses.clone());
+    wait_until(|| load_balanced_channel.snapshot().endpoints == addresses).await;
+}
+
+#[tokio::test]
+async fn mesh_passthrough_connects_to_the_hostname_without_probing() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+    let server = TestServer::start(
+        TesterServer::new(TesterImpl {
+            sender: Arc::new(Mutex::new(sender)),
+            name: "sidecar".to_string(),
+        }),
+        None,
+        None,
+    )
+    .await;
+    let port = server.address().parse::<SocketAddr>().unwrap().port();
+
+    let lookup_service = ginepro::test_util::ScriptedLookupService::new([]);
+    let load_balanced_channel = LoadBalancedChannelBuilder::new_with_service(("127.0.0.1", port))
+        .lookup_service(lookup_service.clone())
+        .mesh_passthrough(true)
+        .channel()
+        .await
+        .expect("failed to init");
+
+    let mut client = TesterClient::new(load_balanced_channel.clone());
+    assert_eq!(
+        HashSet::from(["sidecar".to_string()]),
+        call_servers(&mut client, &mut receiver, 3).await
+    );
+    load_balanced_channel.ready_signal().await;
+    assert!(load_balanced_channel.snapshot().endpoints.is_empty());
+    assert_eq!(0, lookup_service.resolutions());
+}
+
+#[tokio::test]
+async fn endpoints_with_a_scope_id_can_be_connected_to() {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+    let server = TestServer::start(
+        TesterServer::new(TesterImpl {
+            sender: Arc::new(Mutex::new(sender)),
+            name: "server_a".to_string(),
+        }),
+        "[::1]:0".to_string(),
+        None,
+    )
+    .await;
+    let port = server.address().parse::<SocketAddr>().unwrap().port();
+    // The uri of the endpoint, with the host `[::1%1]`, is resolved through `getaddrinfo` as
+    // the one of a link-local address is. The scope id is the index of the loopback interface.
+    let scoped = SocketAddr::V6(std::net::SocketAddrV6::new(
+        std::net::Ipv6Addr::LOCALHOST,
+        port,
+        0,
+        1,
+    ));
+
+    let load_balanced_channel = LoadBalancedChannel::from_endpoints([scoped])
+        .resolution_strategy(ginepro::ResolutionStrategy::Eager {
+            timeout: Duration::from_secs(5),
+        })
+        .channel()
+        .await
+        .expect("failed to init");
+    let mut client = TesterClient::new(load_balanced_channel);
+
+    assert_eq!(
+        HashSet::from(["server_a".to_string()]),
+        call_servers(&mut client, &mut receiver, 5).await
+    );
+}